@@ -0,0 +1,326 @@
+//! Request and response bodies of the slider captcha server's public HTTP API.
+//!
+//! The server reads and writes exactly these types, and the OpenAPI document served at
+//! `/v1/openapi.json` is generated from them, so clients can use them directly instead of
+//! hand-coding the JSON shapes. The `openapi` feature derives the `utoipa` schemas; clients
+//! can leave it off.
+
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+#[cfg(feature = "openapi")]
+use utoipa::{IntoParams, ToSchema};
+
+/// Path prefix of the versioned API; the server also serves the same routes unprefixed.
+pub const API_V1: &str = "/v1";
+
+/// Version of the error envelope layout, reported in every error body.
+pub const ERROR_ENVELOPE_VERSION: u32 = 1;
+
+/// Challenge families a site may request.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(ToSchema))]
+#[serde(rename_all = "lowercase")]
+pub enum ChallengeType {
+    #[default]
+    Slider,
+    Click,
+}
+
+/// Axis the user drags the piece along.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(ToSchema))]
+#[serde(rename_all = "lowercase")]
+pub enum Orientation {
+    #[default]
+    Horizontal,
+    Vertical,
+}
+
+/// Query parameters of `GET /v1/puzzle`.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(IntoParams))]
+#[cfg_attr(feature = "openapi", into_params(parameter_in = Query))]
+pub struct PuzzleQuery {
+    /// Height in pixels.
+    #[serde(default = "default_height")]
+    #[cfg_attr(feature = "openapi", param(default = 300))]
+    pub h: u32,
+    /// Width in pixels.
+    #[serde(default = "default_width")]
+    #[cfg_attr(feature = "openapi", param(default = 500))]
+    pub w: u32,
+    #[serde(default, rename = "type")]
+    #[cfg_attr(feature = "openapi", param(rename = "type", inline))]
+    pub kind: ChallengeType,
+    /// Click challenges: number of icons to click, 2-6.
+    #[serde(default = "default_icon_count")]
+    #[cfg_attr(feature = "openapi", param(default = 4))]
+    pub n: u8,
+    /// Sliders: number of pieces, 1-3.
+    #[serde(default = "default_piece_count")]
+    #[cfg_attr(feature = "openapi", param(default = 1))]
+    pub pieces: u8,
+    #[serde(default)]
+    #[cfg_attr(feature = "openapi", param(inline))]
+    pub orientation: Orientation,
+    /// Site the puzzle is issued for.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sitekey: Option<String>,
+}
+
+impl Default for PuzzleQuery {
+    fn default() -> Self {
+        Self {
+            h: default_height(),
+            w: default_width(),
+            kind: ChallengeType::default(),
+            n: default_icon_count(),
+            pieces: default_piece_count(),
+            orientation: Orientation::default(),
+            sitekey: None,
+        }
+    }
+}
+
+/// Default `h` of [`PuzzleQuery`].
+pub fn default_height() -> u32 {
+    300
+}
+
+/// Default `w` of [`PuzzleQuery`].
+pub fn default_width() -> u32 {
+    500
+}
+
+/// Default `n` of [`PuzzleQuery`].
+pub fn default_icon_count() -> u8 {
+    4
+}
+
+/// Default `pieces` of [`PuzzleQuery`].
+pub fn default_piece_count() -> u8 {
+    1
+}
+
+/// A new challenge. Each image is either inline base64 (`*_image`) or a URL on the binary
+/// image endpoint (`*_url`), depending on the server's image delivery, and always has a
+/// matching `*_mime`.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(ToSchema))]
+pub struct PuzzleResponse {
+    pub id: String,
+    /// `click` for click challenges; absent for sliders.
+    #[serde(rename = "type", default, skip_serializing_if = "Option::is_none")]
+    pub kind: Option<ChallengeType>,
+    /// Background, with holes for sliders and icons drawn in for click challenges.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub puzzle_image: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub puzzle_url: Option<String>,
+    pub puzzle_mime: String,
+    /// Single-piece sliders only.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub piece_image: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub piece_url: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub piece_mime: Option<String>,
+    /// Fixed position of a single vertical piece, relative to the width.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub x: Option<f64>,
+    /// Fixed position of a single horizontal piece, relative to the height.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub y: Option<f64>,
+    /// Multi-piece sliders, in slide order.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pieces: Option<Vec<PieceImage>>,
+    /// Click challenges: icon names in click order.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub icons: Option<Vec<String>>,
+    /// Click challenges: strip showing the icons in click order.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hint_image: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hint_url: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hint_mime: Option<String>,
+    /// Only reported for vertical sliders.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub orientation: Option<Orientation>,
+    /// Transparent pixels on each side of every piece image, for its drop shadow.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub piece_padding: Option<u32>,
+}
+
+/// One piece of a multi-piece slider.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(ToSchema))]
+pub struct PieceImage {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub image: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+    pub mime: String,
+    /// Fixed position of a vertical piece.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub x: Option<f64>,
+    /// Fixed position of a horizontal piece.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub y: Option<f64>,
+}
+
+/// Body of `POST /v1/puzzle/solution`. Sliders submit `x` (or `y` when vertical), click
+/// challenges submit `points`.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(ToSchema))]
+pub struct SolutionRequest {
+    pub id: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub x: Option<SliderSubmission>,
+    /// Used instead of `x` for vertical sliders.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub y: Option<SliderSubmission>,
+    /// Clicked points in order, relative to the image size.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub points: Option<Vec<ClickPoint>>,
+    /// Must name the site the puzzle was issued for, if any.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sitekey: Option<String>,
+}
+
+/// A single position for classic puzzles, or one per piece for multi-piece puzzles.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(ToSchema))]
+#[serde(untagged)]
+pub enum SliderSubmission {
+    Single(f64),
+    Multiple(Vec<f64>),
+}
+
+impl SliderSubmission {
+    pub fn values(&self) -> &[f64] {
+        match self {
+            SliderSubmission::Single(x) => std::slice::from_ref(x),
+            SliderSubmission::Multiple(xs) => xs,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(ToSchema))]
+pub struct ClickPoint {
+    pub x: f64,
+    pub y: f64,
+}
+
+/// A correct answer.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(ToSchema))]
+pub struct VerifyResponse {
+    pub success: bool,
+    pub message: String,
+}
+
+/// Body of `POST /v1/siteverify`, sent by a site's backend.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(ToSchema))]
+pub struct SiteVerifyRequest {
+    pub secret: String,
+    pub id: String,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(ToSchema))]
+pub struct SiteVerifyResponse {
+    pub success: bool,
+    pub sitekey: String,
+}
+
+/// The body of every failed request. Switch on `code`; `error` is for humans.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(ToSchema))]
+pub struct ErrorResponse {
+    /// Always `false`.
+    pub success: bool,
+    /// Envelope layout version, currently 1.
+    pub version: u32,
+    pub code: ErrorCode,
+    pub error: String,
+    /// Extra data for some codes, e.g. `attempts` and `remaining` for `WRONG_ANSWER`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[cfg_attr(feature = "openapi", schema(value_type = Option<Object>))]
+    pub details: Option<serde_json::Value>,
+}
+
+/// Machine-readable reason for a failed request. Values are stable.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(ToSchema))]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum ErrorCode {
+    /// Malformed body or query, or parameters out of range.
+    InvalidRequest,
+    /// Missing or wrong admin bearer token.
+    Unauthorized,
+    /// `/siteverify` was called with an unknown secret key.
+    InvalidSecret,
+    /// `/puzzle` needs a `sitekey` on this server.
+    SiteKeyRequired,
+    InvalidSiteKey,
+    OriginNotAllowed,
+    /// The site does not allow this challenge type or size.
+    NotAllowedForSite,
+    /// No solution with this id, or it belongs to another site.
+    InvalidId,
+    Expired,
+    WrongAnswer,
+    /// The id was discarded after too many wrong answers.
+    TooManyAttempts,
+    /// `/siteverify` for a puzzle the user has not solved yet.
+    NotVerified,
+    RateLimited,
+    NotFound,
+    /// Rendering or encoding the challenge failed.
+    GenerationFailed,
+    /// The generator is busy or stopped; retry later.
+    Unavailable,
+    ShuttingDown,
+    /// A reloaded configuration was rejected; the previous one stays active.
+    InvalidConfig,
+    Internal,
+}
+
+impl ErrorCode {
+    /// Every code, in declaration order.
+    pub const ALL: [ErrorCode; 19] = [
+        ErrorCode::InvalidRequest,
+        ErrorCode::Unauthorized,
+        ErrorCode::InvalidSecret,
+        ErrorCode::SiteKeyRequired,
+        ErrorCode::InvalidSiteKey,
+        ErrorCode::OriginNotAllowed,
+        ErrorCode::NotAllowedForSite,
+        ErrorCode::InvalidId,
+        ErrorCode::Expired,
+        ErrorCode::WrongAnswer,
+        ErrorCode::TooManyAttempts,
+        ErrorCode::NotVerified,
+        ErrorCode::RateLimited,
+        ErrorCode::NotFound,
+        ErrorCode::GenerationFailed,
+        ErrorCode::Unavailable,
+        ErrorCode::ShuttingDown,
+        ErrorCode::InvalidConfig,
+        ErrorCode::Internal,
+    ];
+}
+
+// Through serde, so the text is always the wire name.
+impl fmt::Display for ErrorCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match serde_json::to_value(self) {
+            Ok(serde_json::Value::String(name)) => f.write_str(&name),
+            _ => Err(fmt::Error),
+        }
+    }
+}
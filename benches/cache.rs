@@ -0,0 +1,75 @@
+//! `ExpiringCache` insert/pop throughput, single-threaded and under contention.
+
+use std::{
+    sync::{Arc, Barrier},
+    thread,
+    time::{Duration, Instant},
+};
+
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use slider_captcha_server::cache::ExpiringCache;
+
+/// Roughly the size of an encoded 500x300 PNG puzzle.
+const ENTRY_SIZE: usize = 11 * 1024;
+const KEYS: u32 = 4;
+
+fn new_cache() -> ExpiringCache<u32, Vec<u8>> {
+    ExpiringCache::new(Duration::from_secs(60), 32)
+}
+
+fn insert_pop(c: &mut Criterion) {
+    let cache = new_cache();
+    let entry = vec![0u8; ENTRY_SIZE];
+
+    c.bench_function("cache_insert_pop", |b| {
+        b.iter(|| {
+            cache.insert(1, entry.clone());
+            black_box(cache.pop(&1))
+        })
+    });
+}
+
+fn contention(c: &mut Criterion) {
+    let mut group = c.benchmark_group("cache_contention");
+
+    for threads in [2, 4, 8] {
+        group.throughput(Throughput::Elements(threads as u64));
+        group.bench_with_input(
+            BenchmarkId::from_parameter(threads),
+            &threads,
+            |b, &threads| {
+                b.iter_custom(|iters| {
+                    let cache = new_cache();
+                    let barrier = Arc::new(Barrier::new(threads + 1));
+                    let workers: Vec<_> = (0..threads)
+                        .map(|worker| {
+                            let cache = cache.clone();
+                            let barrier = barrier.clone();
+                            thread::spawn(move || {
+                                let key = worker as u32 % KEYS;
+                                let entry = vec![0u8; ENTRY_SIZE];
+                                barrier.wait();
+                                for _ in 0..iters {
+                                    cache.insert(key, entry.clone());
+                                    black_box(cache.pop(&key));
+                                }
+                            })
+                        })
+                        .collect();
+
+                    barrier.wait();
+                    let start = Instant::now();
+                    for worker in workers {
+                        worker.join().unwrap();
+                    }
+                    start.elapsed()
+                })
+            },
+        );
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, insert_pop, contention);
+criterion_main!(benches);
@@ -0,0 +1,82 @@
+//! Memory held by one cached `PuzzleImages` entry, per size and encoding.
+//!
+//! Run with `cargo bench --bench cache_memory`. The "pixels + b64" column is what an
+//! entry used to retain: the raw `DynamicImage`s inside the puzzle, the encoded bytes
+//! and a base64 copy of them. The "bytes" column is what is cached now.
+
+use std::mem::size_of;
+
+use bytes::Bytes;
+use slider_captcha_server::generator::{
+    generate_puzzle, Challenge, ImageEncoding, PuzzleImages, PuzzleKey, RenderOptions,
+};
+
+const SAMPLES: u64 = 20;
+const QUALITY: u8 = 80;
+
+fn base64_len(len: usize) -> usize {
+    len.div_ceil(3) * 4
+}
+
+/// Bytes owned by the entry today: encoded images, the struct and its metadata.
+fn cached_size(images: &PuzzleImages) -> usize {
+    let metadata = match &images.challenge {
+        Challenge::Slider { pieces, .. } => std::mem::size_of_val(pieces.as_slice()),
+        Challenge::Click { icons, points, .. } => {
+            std::mem::size_of_val(icons.as_slice()) + std::mem::size_of_val(points.as_slice())
+        }
+    };
+    size_of::<PuzzleImages>()
+        + images.pieces.len() * size_of::<Bytes>()
+        + metadata
+        + images.encoded_len()
+}
+
+/// What the previous layout retained for the same slider: RGBA background, RGB pieces,
+/// the encoded bytes and their base64 strings.
+fn previous_size(key: &PuzzleKey, images: &PuzzleImages) -> usize {
+    let (width, height) = (key.width as usize, key.height as usize);
+    let pixels = width * height * 4 + images.pieces.len() * (width / 5) * (height / 5) * 3;
+    let encoded = images.encoded_len();
+    let b64 = base64_len(images.background.len())
+        + images
+            .pieces
+            .iter()
+            .map(|piece| base64_len(piece.len()))
+            .sum::<usize>();
+    pixels + encoded + b64
+}
+
+fn main() {
+    let sizes = [(300, 200), (400, 300), (500, 300), (800, 600)];
+    let encodings = [ImageEncoding::Png, ImageEncoding::Jpeg, ImageEncoding::WebP];
+    let options = RenderOptions {
+        quality: QUALITY,
+        ..RenderOptions::default()
+    };
+
+    println!(
+        "{:<10} {:<6} {:>16} {:>12} {:>8}",
+        "size", "format", "pixels + b64", "bytes", "ratio"
+    );
+    for (width, height) in sizes {
+        for encoding in encodings {
+            let key = PuzzleKey::slider(width, height).with_encoding(encoding);
+            let (mut previous, mut cached) = (0, 0);
+            for seed in 0..SAMPLES {
+                let images = generate_puzzle(key, seed, &options).expect("puzzle");
+                previous += previous_size(&key, &images);
+                cached += cached_size(&images);
+            }
+            let (previous, cached) = (previous / SAMPLES as usize, cached / SAMPLES as usize);
+            println!(
+                "{:<10} {:<6} {:>13} KB {:>9} KB {:>7.1}x",
+                format!("{width}x{height}"),
+                format!("{encoding:?}").to_lowercase(),
+                previous / 1024,
+                cached / 1024,
+                previous as f64 / cached as f64
+            );
+        }
+    }
+}
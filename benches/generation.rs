@@ -0,0 +1,68 @@
+//! Puzzle generation and image encoding, without the HTTP stack.
+
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use image::codecs::png::CompressionType;
+use slider_captcha_server::{
+    generator::{bytes_to_base64, encode_png_with, image_to_base64, ImageFormat},
+    SliderPuzzle,
+};
+
+const SIZES: [(u32, u32); 3] = [(300, 200), (500, 300), (800, 600)];
+
+fn from_dimensions(c: &mut Criterion) {
+    let mut group = c.benchmark_group("slider_from_dimensions");
+    group.throughput(Throughput::Elements(1));
+
+    for (width, height) in SIZES {
+        group.bench_with_input(
+            BenchmarkId::from_parameter(format!("{width}x{height}")),
+            &(width, height),
+            |b, &(width, height)| {
+                b.iter(|| black_box(SliderPuzzle::from_dimensions(width, height).unwrap()))
+            },
+        );
+    }
+
+    group.finish();
+}
+
+fn png_compression(c: &mut Criterion) {
+    let puzzle = SliderPuzzle::from_seed(500, 300, 1).unwrap();
+    let levels = [
+        ("fast", CompressionType::Fast),
+        ("default", CompressionType::Default),
+        ("best", CompressionType::Best),
+        ("huffman", CompressionType::Huffman),
+        ("rle", CompressionType::Rle),
+    ];
+
+    let mut group = c.benchmark_group("png_base64_500x300");
+    for (name, level) in levels {
+        group.bench_function(name, |b| {
+            b.iter(|| {
+                let bytes = encode_png_with(black_box(&puzzle.cropped_puzzle), level).unwrap();
+                black_box(bytes_to_base64(&bytes))
+            })
+        });
+    }
+    // The public helper, which uses the default level.
+    group.bench_function("image_to_base64", |b| {
+        b.iter(|| black_box(image_to_base64(puzzle.cropped_puzzle.clone()).unwrap()))
+    });
+    group.finish();
+}
+
+fn output_formats(c: &mut Criterion) {
+    let puzzle = SliderPuzzle::from_seed(500, 300, 1).unwrap();
+
+    let mut group = c.benchmark_group("encode_500x300");
+    for format in [ImageFormat::Png, ImageFormat::Jpeg, ImageFormat::WebP] {
+        group.bench_function(format.extension(), |b| {
+            b.iter(|| black_box(format.encode(&puzzle.cropped_puzzle, 80).unwrap()))
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, from_dimensions, png_compression, output_formats);
+criterion_main!(benches);
@@ -0,0 +1,33 @@
+//! Slider puzzles rendered per second, per size.
+//!
+//! `cargo bench --bench rasteriser -- --save-baseline <name>` records a run and
+//! `--baseline <name>` compares against it; see `bench/README.md` for recorded numbers.
+
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use slider_captcha_server::SliderPuzzle;
+
+const SIZES: [(u32, u32); 4] = [(300, 200), (400, 300), (500, 300), (800, 600)];
+
+fn slider_generation(c: &mut Criterion) {
+    let mut group = c.benchmark_group("slider_from_seed");
+    group.throughput(Throughput::Elements(1));
+
+    for (width, height) in SIZES {
+        group.bench_with_input(
+            BenchmarkId::from_parameter(format!("{width}x{height}")),
+            &(width, height),
+            |b, &(width, height)| {
+                let mut seed = 0u64;
+                b.iter(|| {
+                    seed = seed.wrapping_add(1);
+                    black_box(SliderPuzzle::from_seed(width, height, seed).unwrap())
+                })
+            },
+        );
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, slider_generation);
+criterion_main!(benches);
@@ -0,0 +1,96 @@
+//! `/puzzle` and `/puzzle/solution` through the actix service stack, in-process.
+
+use std::{
+    sync::Arc,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use actix_web::{rt::System, test, web, App};
+use criterion::{black_box, criterion_group, criterion_main, BatchSize, Criterion};
+use slider_captcha_server::{
+    config::{AppConfig, SharedConfig},
+    generator::{Answer, PuzzleGenerator},
+    rate_limit::RateLimiter,
+    server::{configure, AppState},
+    Orientation,
+};
+
+fn bench_config() -> AppConfig {
+    AppConfig {
+        workers: 1,
+        solution_ttl: Duration::from_secs(60),
+        puzzle_ttl: Duration::from_secs(60),
+        cache_prefill_per_size: 0,
+        generator_concurrency: 4,
+        cleanup_interval: Duration::from_secs(60),
+        prefill_dimensions: vec![],
+        log_level: "warn".into(),
+        ..AppConfig::default()
+    }
+}
+
+fn handlers(c: &mut Criterion) {
+    let system = System::new();
+    let config = Arc::new(bench_config());
+    let state = system.block_on(async {
+        AppState {
+            generator: Arc::new(PuzzleGenerator::new(config.clone())),
+            config: SharedConfig::new(config),
+            rate_limiter: RateLimiter::new(),
+        }
+    });
+    let generator = state.generator.clone();
+    let app = system.block_on(test::init_service(
+        App::new()
+            .app_data(web::Data::new(state))
+            .configure(configure),
+    ));
+
+    let mut group = c.benchmark_group("handlers");
+    group.sample_size(30);
+
+    group.bench_function("puzzle_handler_500x300", |b| {
+        b.iter(|| {
+            system.block_on(async {
+                let req = test::TestRequest::get()
+                    .uri("/puzzle?w=500&h=300")
+                    .to_request();
+                black_box(test::call_and_read_body(&app, req).await)
+            })
+        })
+    });
+
+    group.bench_function("verify_handler", |b| {
+        b.iter_batched(
+            || {
+                let id = uuid::Uuid::new_v4().to_string();
+                let expires_at = SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .unwrap()
+                    .as_secs()
+                    + 60;
+                let answer = Answer::Slider {
+                    targets: vec![0.5],
+                    orientation: Orientation::Horizontal,
+                };
+                generator.cache_solution(id.clone(), answer, expires_at, None, None);
+                id
+            },
+            |id| {
+                system.block_on(async {
+                    let req = test::TestRequest::post()
+                        .uri("/puzzle/solution")
+                        .set_json(serde_json::json!({ "id": id, "x": 0.5 }))
+                        .to_request();
+                    black_box(test::call_and_read_body(&app, req).await)
+                })
+            },
+            BatchSize::SmallInput,
+        )
+    });
+
+    group.finish();
+}
+
+criterion_group!(benches, handlers);
+criterion_main!(benches);
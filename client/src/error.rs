@@ -0,0 +1,78 @@
+use std::{fmt, time::Duration};
+
+use slider_captcha_api::{ErrorCode, ErrorResponse};
+
+/// Why a call to the captcha server failed.
+#[derive(Debug)]
+pub enum Error {
+    /// The server rejected the request; switch on `error.code`.
+    Api {
+        status: u16,
+        error: ErrorResponse,
+        /// From the `Retry-After` header, sent with `RATE_LIMITED`.
+        retry_after: Option<Duration>,
+    },
+    /// The request could not be sent or its response could not be read.
+    Http(reqwest::Error),
+    /// A response that is not the API's JSON, e.g. from a proxy in front of the server.
+    UnexpectedResponse { status: u16, body: String },
+}
+
+impl Error {
+    /// The server's error code, if the server answered with one.
+    pub fn code(&self) -> Option<ErrorCode> {
+        match self {
+            Error::Api { error, .. } => Some(error.code),
+            _ => None,
+        }
+    }
+
+    /// Whether the request is worth sending again. `idempotent` requests are also retried
+    /// after timeouts and gateway errors, where the server may already have handled them.
+    pub(crate) fn is_retryable(&self, idempotent: bool) -> bool {
+        match self {
+            Error::Api { error, .. } => matches!(
+                error.code,
+                ErrorCode::RateLimited | ErrorCode::Unavailable | ErrorCode::ShuttingDown
+            ),
+            Error::Http(err) => err.is_connect() || (idempotent && err.is_timeout()),
+            Error::UnexpectedResponse { status, .. } => idempotent && matches!(status, 502..=504),
+        }
+    }
+
+    pub(crate) fn retry_after(&self) -> Option<Duration> {
+        match self {
+            Error::Api { retry_after, .. } => *retry_after,
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Api { status, error, .. } => {
+                write!(f, "{} ({status}): {}", error.code, error.error)
+            }
+            Error::Http(err) => write!(f, "request failed: {err}"),
+            Error::UnexpectedResponse { status, body } => {
+                write!(f, "unexpected response ({status}): {body}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Http(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<reqwest::Error> for Error {
+    fn from(err: reqwest::Error) -> Self {
+        Error::Http(err)
+    }
+}
@@ -0,0 +1,170 @@
+//! Client for the slider captcha server's `/v1` API.
+//!
+//! Requests and responses are the server's own [`api`] types, so they cannot drift from
+//! what the server sends.
+//!
+//! ```no_run
+//! use slider_captcha_client::{Client, PuzzleQuery, SiteVerifyRequest};
+//!
+//! # async fn example() -> Result<(), slider_captcha_client::Error> {
+//! let client = Client::new("http://127.0.0.1:8080");
+//! let puzzle = client
+//!     .fetch_puzzle(&PuzzleQuery {
+//!         sitekey: Some("shop-public".into()),
+//!         ..PuzzleQuery::default()
+//!     })
+//!     .await?;
+//! // ...the browser solves `puzzle` and submits it; then the site's backend redeems it:
+//! let verified = client
+//!     .siteverify(&SiteVerifyRequest {
+//!         secret: "shop-secret".into(),
+//!         id: puzzle.id,
+//!     })
+//!     .await?;
+//! assert!(verified.success);
+//! # Ok(())
+//! # }
+//! ```
+
+use std::time::Duration;
+
+use reqwest::{header, RequestBuilder, Response};
+use serde::de::DeserializeOwned;
+use tracing::debug;
+
+mod error;
+mod retry;
+
+pub use error::Error;
+pub use retry::RetryPolicy;
+pub use slider_captcha_api::{
+    self as api, ChallengeType, ClickPoint, ErrorCode, ErrorResponse, Orientation, PieceImage,
+    PuzzleQuery, PuzzleResponse, SiteVerifyRequest, SiteVerifyResponse, SliderSubmission,
+    SolutionRequest, VerifyResponse,
+};
+
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// A handle to one captcha server. Cheap to clone; clones share the connection pool.
+#[derive(Clone, Debug)]
+pub struct Client {
+    http: reqwest::Client,
+    base_url: String,
+    timeout: Duration,
+    retry: RetryPolicy,
+}
+
+impl Client {
+    /// A client for the server at `base_url`, e.g. `http://127.0.0.1:8080`.
+    pub fn new(base_url: impl Into<String>) -> Self {
+        Self::with_http_client(base_url, reqwest::Client::new())
+    }
+
+    /// Like [`Client::new`], sending through an existing `reqwest` client.
+    pub fn with_http_client(base_url: impl Into<String>, http: reqwest::Client) -> Self {
+        let base_url = base_url.into().trim_end_matches('/').to_string();
+        Self {
+            http,
+            base_url,
+            timeout: DEFAULT_TIMEOUT,
+            retry: RetryPolicy::default(),
+        }
+    }
+
+    /// Per-attempt timeout; 10 seconds by default.
+    pub fn with_timeout(self, timeout: Duration) -> Self {
+        Self { timeout, ..self }
+    }
+
+    pub fn with_retry(self, retry: RetryPolicy) -> Self {
+        Self { retry, ..self }
+    }
+
+    pub fn base_url(&self) -> &str {
+        &self.base_url
+    }
+
+    /// `GET /v1/puzzle`.
+    pub async fn fetch_puzzle(&self, query: &PuzzleQuery) -> Result<PuzzleResponse, Error> {
+        self.send(true, || self.http.get(self.url("/puzzle")).query(query))
+            .await
+    }
+
+    /// `POST /v1/puzzle/solution`. A wrong answer is an [`Error::Api`] with code
+    /// `WRONG_ANSWER` and the remaining attempts in `details`.
+    pub async fn submit_solution(
+        &self,
+        solution: &SolutionRequest,
+    ) -> Result<VerifyResponse, Error> {
+        self.send(false, || {
+            self.http.post(self.url("/puzzle/solution")).json(solution)
+        })
+        .await
+    }
+
+    /// `POST /v1/siteverify`, from the site's backend. Each solved id can be redeemed once.
+    pub async fn siteverify(
+        &self,
+        request: &SiteVerifyRequest,
+    ) -> Result<SiteVerifyResponse, Error> {
+        self.send(false, || {
+            self.http.post(self.url("/siteverify")).json(request)
+        })
+        .await
+    }
+
+    fn url(&self, path: &str) -> String {
+        format!("{}{}{path}", self.base_url, api::API_V1)
+    }
+
+    async fn send<T: DeserializeOwned>(
+        &self,
+        idempotent: bool,
+        request: impl Fn() -> RequestBuilder,
+    ) -> Result<T, Error> {
+        let mut retry = 0;
+        loop {
+            let result = match request().timeout(self.timeout).send().await {
+                Ok(response) => read(response).await,
+                Err(err) => Err(Error::Http(err)),
+            };
+            let err = match result {
+                Err(err) if err.is_retryable(idempotent) => err,
+                result => return result,
+            };
+            let Some(delay) = self.retry.delay(retry, err.retry_after()) else {
+                return Err(err);
+            };
+            debug!(error = %err, retry, delay_ms = delay.as_millis(), "Retrying captcha request");
+            tokio::time::sleep(delay).await;
+            retry += 1;
+        }
+    }
+}
+
+async fn read<T: DeserializeOwned>(response: Response) -> Result<T, Error> {
+    let status = response.status();
+    let retry_after = response
+        .headers()
+        .get(header::RETRY_AFTER)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.parse().ok())
+        .map(Duration::from_secs);
+    let body = response.bytes().await?;
+
+    let unexpected = || Error::UnexpectedResponse {
+        status: status.as_u16(),
+        body: String::from_utf8_lossy(&body).into_owned(),
+    };
+    if status.is_success() {
+        return serde_json::from_slice(&body).map_err(|_| unexpected());
+    }
+    match serde_json::from_slice::<ErrorResponse>(&body) {
+        Ok(error) => Err(Error::Api {
+            status: status.as_u16(),
+            error,
+            retry_after,
+        }),
+        Err(_) => Err(unexpected()),
+    }
+}
@@ -0,0 +1,55 @@
+use std::time::Duration;
+
+/// How often and how long to wait before resending a failed request.
+///
+/// Requests are retried when the server did not handle them: connection failures,
+/// `RATE_LIMITED`, `UNAVAILABLE` and `SHUTTING_DOWN`. [`Client::fetch_puzzle`] is also
+/// retried after timeouts and gateway errors; submissions and site verifications are not,
+/// because the server may already have counted them.
+///
+/// [`Client::fetch_puzzle`]: crate::Client::fetch_puzzle
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// Retries after the first attempt; 0 disables retrying.
+    pub max_retries: u32,
+    /// Wait before the first retry, doubled for each further one.
+    pub initial_backoff: Duration,
+    /// Longest wait between attempts. A `Retry-After` beyond it is returned to the caller
+    /// instead of being waited out.
+    pub max_backoff: Duration,
+}
+
+impl RetryPolicy {
+    pub fn none() -> Self {
+        Self {
+            max_retries: 0,
+            ..Self::default()
+        }
+    }
+
+    /// The wait before retry number `retry` (from 0), or `None` to give up.
+    pub(crate) fn delay(&self, retry: u32, retry_after: Option<Duration>) -> Option<Duration> {
+        if retry >= self.max_retries {
+            return None;
+        }
+        match retry_after {
+            Some(wait) if wait > self.max_backoff => None,
+            Some(wait) => Some(wait),
+            None => Some(
+                self.initial_backoff
+                    .saturating_mul(2u32.saturating_pow(retry))
+                    .min(self.max_backoff),
+            ),
+        }
+    }
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_retries: 2,
+            initial_backoff: Duration::from_millis(100),
+            max_backoff: Duration::from_secs(2),
+        }
+    }
+}
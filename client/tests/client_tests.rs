@@ -0,0 +1,205 @@
+use std::{
+    future,
+    net::TcpListener,
+    sync::{
+        atomic::{AtomicU32, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+use actix_web::{rt, web, App, HttpResponse, HttpServer};
+use slider_captcha_client::{
+    Client, ErrorCode, ErrorResponse, PuzzleQuery, PuzzleResponse, RetryPolicy, SiteVerifyRequest,
+    SliderSubmission, SolutionRequest,
+};
+use slider_captcha_server::{
+    config::{AppConfig, SharedConfig},
+    generator::Answer,
+    server::run_server_on,
+    site::SiteRegistry,
+};
+
+const ADMIN_TOKEN: &str = "0123456789abcdef";
+
+fn test_config() -> AppConfig {
+    AppConfig {
+        workers: 1,
+        prefill_dimensions: vec![],
+        admin_token: Some(ADMIN_TOKEN.into()),
+        sites: SiteRegistry::new(vec![serde_json::from_value(serde_json::json!({
+            "site_key": "site-a",
+            "secret_key": "secret-a",
+        }))
+        .unwrap()])
+        .unwrap(),
+        ..AppConfig::default()
+    }
+}
+
+/// Runs the real server on an ephemeral port and returns its base URL.
+fn spawn_server(config: AppConfig) -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let base_url = format!("http://{}", listener.local_addr().unwrap());
+    rt::spawn(run_server_on(
+        SharedConfig::new(Arc::new(config)),
+        listener,
+        future::pending(),
+    ));
+    base_url
+}
+
+fn slider(x: f64, id: &str) -> SolutionRequest {
+    SolutionRequest {
+        id: id.into(),
+        x: Some(SliderSubmission::Single(x)),
+        y: None,
+        points: None,
+        sitekey: Some("site-a".into()),
+    }
+}
+
+#[actix_web::test]
+async fn fetch_submit_and_siteverify_against_run_server() {
+    let base_url = spawn_server(test_config());
+    let client = Client::new(&base_url);
+
+    let puzzle = client
+        .fetch_puzzle(&PuzzleQuery {
+            w: 200,
+            h: 150,
+            sitekey: Some("site-a".into()),
+            ..PuzzleQuery::default()
+        })
+        .await
+        .unwrap();
+    assert!(puzzle.puzzle_image.is_some());
+
+    let err = client
+        .submit_solution(&slider(-1.0, &puzzle.id))
+        .await
+        .unwrap_err();
+    assert_eq!(err.code(), Some(ErrorCode::WrongAnswer));
+    let slider_captcha_client::Error::Api { status, error, .. } = &err else {
+        panic!("expected an API error, got {err}");
+    };
+    assert_eq!(*status, 422);
+    assert_eq!(error.details.as_ref().unwrap()["attempts"], 1);
+
+    // The client has no way to learn the answer; ask the admin API for it.
+    let revealed: serde_json::Value = reqwest::Client::new()
+        .get(format!(
+            "{base_url}/admin/solutions/{}?reveal=true",
+            puzzle.id
+        ))
+        .bearer_auth(ADMIN_TOKEN)
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    let Answer::Slider { targets, .. } =
+        serde_json::from_value(revealed["answer"].clone()).unwrap()
+    else {
+        panic!("expected a slider answer");
+    };
+    let verified = client
+        .submit_solution(&slider(targets[0], &puzzle.id))
+        .await
+        .unwrap();
+    assert!(verified.success);
+
+    let redeem = SiteVerifyRequest {
+        secret: "secret-a".into(),
+        id: puzzle.id.clone(),
+    };
+    let site = client.siteverify(&redeem).await.unwrap();
+    assert_eq!(site.sitekey, "site-a");
+    let err = client.siteverify(&redeem).await.unwrap_err();
+    assert_eq!(err.code(), Some(ErrorCode::InvalidId));
+}
+
+#[actix_web::test]
+async fn long_retry_after_is_returned_instead_of_waited_out() {
+    let mut config = test_config();
+    config.rate_limit_per_minute = 1;
+    let client = Client::new(spawn_server(config));
+    let query = PuzzleQuery {
+        w: 200,
+        h: 150,
+        ..PuzzleQuery::default()
+    };
+
+    client.fetch_puzzle(&query).await.unwrap();
+    let started = Instant::now();
+    let err = client.fetch_puzzle(&query).await.unwrap_err();
+    assert_eq!(err.code(), Some(ErrorCode::RateLimited));
+    assert!(matches!(
+        err,
+        slider_captcha_client::Error::Api {
+            retry_after: Some(_),
+            ..
+        }
+    ));
+    assert!(started.elapsed() < Duration::from_secs(1));
+}
+
+#[actix_web::test]
+async fn unavailable_responses_are_retried() {
+    let calls = Arc::new(AtomicU32::new(0));
+    let counter = calls.clone();
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let base_url = format!("http://{}", listener.local_addr().unwrap());
+    let server = HttpServer::new(move || {
+        let counter = counter.clone();
+        App::new().route(
+            "/v1/puzzle",
+            web::get().to(move || {
+                let call = counter.fetch_add(1, Ordering::SeqCst);
+                async move {
+                    if call < 2 {
+                        HttpResponse::ServiceUnavailable().json(ErrorResponse {
+                            success: false,
+                            version: 1,
+                            code: ErrorCode::Unavailable,
+                            error: "busy".into(),
+                            details: None,
+                        })
+                    } else {
+                        HttpResponse::Ok().json(PuzzleResponse {
+                            id: "stub".into(),
+                            puzzle_mime: "image/png".into(),
+                            ..PuzzleResponse::default()
+                        })
+                    }
+                }
+            }),
+        )
+    })
+    .workers(1)
+    .listen(listener)
+    .unwrap()
+    .run();
+    rt::spawn(server);
+
+    let retry = RetryPolicy {
+        max_retries: 2,
+        initial_backoff: Duration::from_millis(10),
+        max_backoff: Duration::from_millis(50),
+    };
+    let client = Client::new(&base_url).with_retry(retry);
+    let puzzle = client.fetch_puzzle(&PuzzleQuery::default()).await.unwrap();
+    assert_eq!(puzzle.id, "stub");
+    assert_eq!(calls.load(Ordering::SeqCst), 3);
+
+    // Not the API's JSON, and not retried.
+    let err = client
+        .submit_solution(&slider(0.5, "stub"))
+        .await
+        .unwrap_err();
+    assert!(matches!(
+        err,
+        slider_captcha_client::Error::UnexpectedResponse { status: 404, .. }
+    ));
+}
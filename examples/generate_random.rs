@@ -31,7 +31,7 @@ fn main() {
 
     // Save using optimized PNG encoder
     save_optimized_png(&slider_puzzle.cropped_puzzle, &puzzle_path);
-    save_optimized_png(&slider_puzzle.puzzle_piece, &piece_path);
+    save_optimized_png(&slider_puzzle.pieces[0].image, &piece_path);
 
     // Get file sizes
     let puzzle_size = fs::metadata(&puzzle_path).map(|m| m.len()).unwrap_or(0);
@@ -48,17 +48,13 @@ fn main() {
         piece_size / 1024
     );
 
+    let piece = &slider_puzzle.pieces[0];
+
     println!("\nSolution information:");
-    println!("- X position (relative): {:.4}", slider_puzzle.x);
-    println!("- Y position (relative): {:.4}", slider_puzzle.y);
-    println!(
-        "- X position (pixels): {:.0}px",
-        slider_puzzle.x * width as f64
-    );
-    println!(
-        "- Y position (pixels): {:.0}px",
-        slider_puzzle.y * height as f64
-    );
+    println!("- X position (relative): {:.4}", piece.x);
+    println!("- Y position (relative): {:.4}", piece.y);
+    println!("- X position (pixels): {:.0}px", piece.x * width as f64);
+    println!("- Y position (pixels): {:.0}px", piece.y * height as f64);
 
     println!("\nFile size statistics:");
     println!("- Total size: {}KB", (puzzle_size + piece_size) / 1024);
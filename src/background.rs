@@ -0,0 +1,522 @@
+//! Procedural puzzle backgrounds.
+//!
+//! Every generator draws all of its randomness from the `rng` it is handed, so a seeded
+//! puzzle stays reproducible whichever style is picked. [`WeightedBackgrounds`] chooses a
+//! style per puzzle so a solver cannot specialise on a single pattern.
+
+use std::{f32::consts::PI, fmt, sync::Arc};
+
+use image::RgbaImage;
+use rand::{Rng, RngCore};
+
+use crate::click::in_polygon;
+
+/// Renders an opaque background of the requested size.
+pub trait BackgroundGenerator: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    fn generate(&self, width: u32, height: u32, rng: &mut dyn RngCore) -> RgbaImage;
+}
+
+/// Built-in generators, selectable by name from configuration.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum BackgroundStyle {
+    /// Two-colour gradient with translucent circles; the original background.
+    Gradient,
+    /// Multi-octave value noise mapped onto a three-colour palette.
+    Noise,
+    /// Voronoi cells with darkened borders.
+    Voronoi,
+    /// Overlapping translucent random polygons.
+    Polygons,
+    /// Angled stripes with a wavy, per-stripe jittered edge.
+    Stripes,
+}
+
+impl BackgroundStyle {
+    pub const ALL: [BackgroundStyle; 5] = [
+        BackgroundStyle::Gradient,
+        BackgroundStyle::Noise,
+        BackgroundStyle::Voronoi,
+        BackgroundStyle::Polygons,
+        BackgroundStyle::Stripes,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            BackgroundStyle::Gradient => "gradient",
+            BackgroundStyle::Noise => "noise",
+            BackgroundStyle::Voronoi => "voronoi",
+            BackgroundStyle::Polygons => "polygons",
+            BackgroundStyle::Stripes => "stripes",
+        }
+    }
+
+    pub fn parse(raw: &str) -> Option<Self> {
+        let raw = raw.trim();
+        Self::ALL
+            .into_iter()
+            .find(|style| style.name().eq_ignore_ascii_case(raw))
+    }
+
+    pub fn generator(self) -> Arc<dyn BackgroundGenerator> {
+        match self {
+            BackgroundStyle::Gradient => Arc::new(Gradient),
+            BackgroundStyle::Noise => Arc::new(FractalNoise),
+            BackgroundStyle::Voronoi => Arc::new(Voronoi),
+            BackgroundStyle::Polygons => Arc::new(Polygons),
+            BackgroundStyle::Stripes => Arc::new(Stripes),
+        }
+    }
+}
+
+/// Parses `gradient,noise:2,voronoi` into styles with weights (default weight 1).
+///
+/// Unknown names and zero weights are skipped.
+pub fn parse_background_styles(raw: &str) -> Vec<(BackgroundStyle, u32)> {
+    raw.split(',')
+        .filter_map(|part| {
+            let (name, weight) = match part.split_once(':') {
+                Some((name, weight)) => (name, weight.trim().parse::<u32>().ok()?),
+                None => (part, 1),
+            };
+            let style = BackgroundStyle::parse(name)?;
+            (weight > 0).then_some((style, weight))
+        })
+        .collect()
+}
+
+/// Like [`parse_background_styles`], but rejects unknown styles, bad weights and an
+/// empty list instead of skipping them.
+pub fn try_parse_background_styles(raw: &str) -> Result<Vec<(BackgroundStyle, u32)>, String> {
+    raw.split(',')
+        .map(|part| {
+            let (name, weight) = match part.split_once(':') {
+                Some((name, weight)) => {
+                    let weight = weight
+                        .trim()
+                        .parse::<u32>()
+                        .ok()
+                        .filter(|weight| *weight > 0)
+                        .ok_or_else(|| format!("invalid weight in {:?}", part.trim()))?;
+                    (name, weight)
+                }
+                None => (part, 1),
+            };
+            let style = BackgroundStyle::parse(name)
+                .ok_or_else(|| format!("unknown background style {:?}", name.trim()))?;
+            Ok((style, weight))
+        })
+        .collect()
+}
+
+/// A weighted set of generators; one is drawn from the puzzle's RNG per background.
+#[derive(Clone)]
+pub struct WeightedBackgrounds {
+    entries: Vec<(Arc<dyn BackgroundGenerator>, u32)>,
+}
+
+impl WeightedBackgrounds {
+    /// An empty set, which renders [`Gradient`] until something is added.
+    pub fn new() -> Self {
+        Self {
+            entries: Vec::new(),
+        }
+    }
+
+    pub fn from_styles(styles: &[(BackgroundStyle, u32)]) -> Self {
+        styles.iter().fold(Self::new(), |set, &(style, weight)| {
+            set.with(style.generator(), weight)
+        })
+    }
+
+    /// Adds a generator, including custom [`BackgroundGenerator`] implementations.
+    pub fn with(mut self, generator: Arc<dyn BackgroundGenerator>, weight: u32) -> Self {
+        if weight > 0 {
+            self.entries.push((generator, weight));
+        }
+        self
+    }
+
+    pub fn pick(&self, rng: &mut dyn RngCore) -> &dyn BackgroundGenerator {
+        let total: u32 = self.entries.iter().map(|(_, weight)| weight).sum();
+        if total == 0 {
+            return &Gradient;
+        }
+
+        let mut roll = rng.gen_range(0..total);
+        for (generator, weight) in &self.entries {
+            if roll < *weight {
+                return generator.as_ref();
+            }
+            roll -= weight;
+        }
+        unreachable!("roll is below the total weight")
+    }
+
+    pub fn generate(&self, width: u32, height: u32, rng: &mut dyn RngCore) -> RgbaImage {
+        self.pick(rng).generate(width, height, rng)
+    }
+}
+
+impl Default for WeightedBackgrounds {
+    /// Every built-in style with equal weight.
+    fn default() -> Self {
+        Self::from_styles(&BackgroundStyle::ALL.map(|style| (style, 1)))
+    }
+}
+
+impl fmt::Debug for WeightedBackgrounds {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map()
+            .entries(
+                self.entries
+                    .iter()
+                    .map(|(generator, weight)| (generator.name(), weight)),
+            )
+            .finish()
+    }
+}
+
+fn random_colour(rng: &mut dyn RngCore, range: std::ops::RangeInclusive<u8>) -> [f32; 3] {
+    [
+        rng.gen_range(range.clone()) as f32,
+        rng.gen_range(range.clone()) as f32,
+        rng.gen_range(range) as f32,
+    ]
+}
+
+fn lerp_colour(from: [f32; 3], to: [f32; 3], t: f32) -> [f32; 3] {
+    [
+        from[0] + (to[0] - from[0]) * t,
+        from[1] + (to[1] - from[1]) * t,
+        from[2] + (to[2] - from[2]) * t,
+    ]
+}
+
+fn to_pixel(colour: [f32; 3]) -> [u8; 4] {
+    [
+        colour[0].clamp(0.0, 255.0) as u8,
+        colour[1].clamp(0.0, 255.0) as u8,
+        colour[2].clamp(0.0, 255.0) as u8,
+        255,
+    ]
+}
+
+/// Fills every pixel from `shade(x, y)`, one row slice at a time.
+fn fill(width: u32, height: u32, mut shade: impl FnMut(u32, u32) -> [u8; 4]) -> RgbaImage {
+    let mut image = RgbaImage::new(width, height);
+    for (y, row) in image.chunks_exact_mut(width as usize * 4).enumerate() {
+        for (x, pixel) in row.chunks_exact_mut(4).enumerate() {
+            pixel.copy_from_slice(&shade(x as u32, y as u32));
+        }
+    }
+    image
+}
+
+/// Blends `colour` over the pixels of `image` selected by `inside`, within a bounding box.
+fn blend_shape(
+    image: &mut RgbaImage,
+    bounds: (i32, i32, i32, i32),
+    colour: [i32; 3],
+    alpha: i32,
+    inside: impl Fn(i32, i32) -> bool,
+) {
+    let (width, height) = (image.width() as i32, image.height() as i32);
+    let row_len = width as usize * 4;
+    let blend = alpha as f32 / 255.0;
+    let mix = |shape: i32, old: u8| (shape as f32 * blend + old as f32 * (1.0 - blend)) as u8;
+
+    let (x0, y0) = (bounds.0.max(0), bounds.1.max(0));
+    let (x1, y1) = (bounds.2.min(width - 1), bounds.3.min(height - 1));
+    let pixels: &mut [u8] = image;
+    for y in y0..=y1 {
+        let row = &mut pixels[y as usize * row_len..(y as usize + 1) * row_len];
+        for x in x0..=x1 {
+            if inside(x, y) {
+                let pixel = &mut row[x as usize * 4..x as usize * 4 + 4];
+                pixel[0] = mix(colour[0], pixel[0]);
+                pixel[1] = mix(colour[1], pixel[1]);
+                pixel[2] = mix(colour[2], pixel[2]);
+            }
+        }
+    }
+}
+
+/// Gradient background with a few translucent circles.
+///
+/// Rows are written as slices and each circle only visits its bounding box; the RNG
+/// draw order is part of the seeded-output contract and must not change.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Gradient;
+
+impl BackgroundGenerator for Gradient {
+    fn name(&self) -> &'static str {
+        BackgroundStyle::Gradient.name()
+    }
+
+    fn generate(&self, width: u32, height: u32, rng: &mut dyn RngCore) -> RgbaImage {
+        let mut image = RgbaImage::new(width, height);
+
+        let r1 = rng.gen_range(100..=255);
+        let g1 = rng.gen_range(100..=255);
+        let b1 = rng.gen_range(100..=255);
+
+        let r2 = rng.gen_range(50..=200);
+        let g2 = rng.gen_range(50..=200);
+        let b2 = rng.gen_range(50..=200);
+
+        let gradient_type = rng.gen_range(0..3);
+
+        let lerp = |ratio: f32| {
+            [
+                (r1 as f32 * (1.0 - ratio) + r2 as f32 * ratio) as u8,
+                (g1 as f32 * (1.0 - ratio) + g2 as f32 * ratio) as u8,
+                (b1 as f32 * (1.0 - ratio) + b2 as f32 * ratio) as u8,
+                255,
+            ]
+        };
+        let rows = image.chunks_exact_mut(width as usize * 4);
+
+        match gradient_type {
+            0 => {
+                let first: Vec<u8> = (0..width)
+                    .flat_map(|x| lerp(x as f32 / width as f32))
+                    .collect();
+                for row in rows {
+                    row.copy_from_slice(&first);
+                }
+            }
+            1 => {
+                for (y, row) in rows.enumerate() {
+                    let pixel = lerp(y as f32 / height as f32);
+                    for chunk in row.chunks_exact_mut(4) {
+                        chunk.copy_from_slice(&pixel);
+                    }
+                }
+            }
+            _ => {
+                for (y, row) in rows.enumerate() {
+                    let ry = y as f32 / height as f32;
+                    for (x, chunk) in row.chunks_exact_mut(4).enumerate() {
+                        chunk.copy_from_slice(&lerp((x as f32 / width as f32 + ry) / 2.0));
+                    }
+                }
+            }
+        }
+
+        let num_shapes = rng.gen_range(2..=4);
+        for _ in 0..num_shapes {
+            let shape_r = rng.gen_range(0..=255);
+            let shape_g = rng.gen_range(0..=255);
+            let shape_b = rng.gen_range(0..=255);
+            let alpha = rng.gen_range(100..=200);
+
+            let cx = rng.gen_range(0..width) as i32;
+            let cy = rng.gen_range(0..height) as i32;
+            let radius: i32 = rng.gen_range(20..60);
+            let radius_sq = (radius * radius) as f32;
+
+            blend_shape(
+                &mut image,
+                (cx - radius, cy - radius, cx + radius, cy + radius),
+                [shape_r, shape_g, shape_b],
+                alpha,
+                |x, y| {
+                    let (dx, dy) = (x - cx, y - cy);
+                    ((dx * dx + dy * dy) as f32) <= radius_sq
+                },
+            );
+        }
+
+        image
+    }
+}
+
+/// Fractal value noise: several octaves of smoothed lattice noise, each half the cell size.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct FractalNoise;
+
+impl BackgroundGenerator for FractalNoise {
+    fn name(&self) -> &'static str {
+        BackgroundStyle::Noise.name()
+    }
+
+    fn generate(&self, width: u32, height: u32, rng: &mut dyn RngCore) -> RgbaImage {
+        const OCTAVES: usize = 4;
+
+        let palette = [
+            random_colour(rng, 60..=255),
+            random_colour(rng, 30..=220),
+            random_colour(rng, 60..=255),
+        ];
+        let base_cell: f32 = rng.gen_range(24.0..64.0);
+
+        let octaves: Vec<(f32, usize, Vec<f32>)> = (0..OCTAVES)
+            .map(|octave| {
+                let cell = (base_cell / (1 << octave) as f32).max(2.0);
+                let columns = (width as f32 / cell) as usize + 2;
+                let rows = (height as f32 / cell) as usize + 2;
+                let lattice = (0..columns * rows).map(|_| rng.gen::<f32>()).collect();
+                (cell, columns, lattice)
+            })
+            .collect();
+
+        let smooth = |t: f32| t * t * (3.0 - 2.0 * t);
+        fill(width, height, |x, y| {
+            let mut value = 0.0;
+            let mut amplitude = 1.0;
+            let mut total = 0.0;
+            for (cell, columns, lattice) in &octaves {
+                let (fx, fy) = (x as f32 / cell, y as f32 / cell);
+                let (ix, iy) = (fx as usize, fy as usize);
+                let (tx, ty) = (smooth(fx.fract()), smooth(fy.fract()));
+                let at = |cx: usize, cy: usize| lattice[cy * columns + cx];
+                let top = at(ix, iy) + (at(ix + 1, iy) - at(ix, iy)) * tx;
+                let bottom = at(ix, iy + 1) + (at(ix + 1, iy + 1) - at(ix, iy + 1)) * tx;
+                value += (top + (bottom - top) * ty) * amplitude;
+                total += amplitude;
+                amplitude *= 0.5;
+            }
+
+            let t = value / total;
+            let colour = if t < 0.5 {
+                lerp_colour(palette[0], palette[1], t * 2.0)
+            } else {
+                lerp_colour(palette[1], palette[2], (t - 0.5) * 2.0)
+            };
+            to_pixel(colour)
+        })
+    }
+}
+
+/// Voronoi cells around random sites, each with its own colour and a shaded border.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Voronoi;
+
+impl BackgroundGenerator for Voronoi {
+    fn name(&self) -> &'static str {
+        BackgroundStyle::Voronoi.name()
+    }
+
+    fn generate(&self, width: u32, height: u32, rng: &mut dyn RngCore) -> RgbaImage {
+        let count = rng.gen_range(12..=32);
+        let sites: Vec<(f32, f32, [f32; 3])> = (0..count)
+            .map(|_| {
+                (
+                    rng.gen_range(0.0..width as f32),
+                    rng.gen_range(0.0..height as f32),
+                    random_colour(rng, 50..=240),
+                )
+            })
+            .collect();
+        let border: f32 = rng.gen_range(3.0..8.0);
+
+        fill(width, height, |x, y| {
+            let (mut nearest, mut second) = ((f32::MAX, [0.0; 3]), f32::MAX);
+            for &(sx, sy, colour) in &sites {
+                let (dx, dy) = (sx - x as f32, sy - y as f32);
+                let distance = (dx * dx + dy * dy).sqrt();
+                if distance < nearest.0 {
+                    second = nearest.0;
+                    nearest = (distance, colour);
+                } else if distance < second {
+                    second = distance;
+                }
+            }
+
+            let edge = ((second - nearest.0) / border).min(1.0);
+            let [r, g, b] = nearest.1;
+            let shade = 0.7 + 0.3 * edge;
+            to_pixel([r * shade, g * shade, b * shade])
+        })
+    }
+}
+
+/// Overlapping translucent polygons with 3-6 vertices over a flat base colour.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Polygons;
+
+impl BackgroundGenerator for Polygons {
+    fn name(&self) -> &'static str {
+        BackgroundStyle::Polygons.name()
+    }
+
+    fn generate(&self, width: u32, height: u32, rng: &mut dyn RngCore) -> RgbaImage {
+        let base = to_pixel(random_colour(rng, 80..=220));
+        let mut image = fill(width, height, |_, _| base);
+
+        let span = width.min(height) as f32;
+        let count = rng.gen_range(10..=24);
+        for _ in 0..count {
+            let sides = rng.gen_range(3..=6);
+            let cx = rng.gen_range(0.0..width as f32);
+            let cy = rng.gen_range(0.0..height as f32);
+            let radius = rng.gen_range(0.1..0.35) * span;
+            let rotation = rng.gen_range(0.0..2.0 * PI);
+            let vertices: Vec<(f32, f32)> = (0..sides)
+                .map(|side| {
+                    let angle = rotation + 2.0 * PI * side as f32 / sides as f32;
+                    let reach = radius * rng.gen_range(0.6..1.0);
+                    (cx + reach * angle.cos(), cy + reach * angle.sin())
+                })
+                .collect();
+            let colour = random_colour(rng, 0..=255).map(|channel| channel as i32);
+            let alpha = rng.gen_range(90..=200);
+
+            let (x0, x1) = vertices.iter().fold((f32::MAX, f32::MIN), |(lo, hi), v| {
+                (lo.min(v.0), hi.max(v.0))
+            });
+            let (y0, y1) = vertices.iter().fold((f32::MAX, f32::MIN), |(lo, hi), v| {
+                (lo.min(v.1), hi.max(v.1))
+            });
+
+            blend_shape(
+                &mut image,
+                (x0 as i32, y0 as i32, x1.ceil() as i32, y1.ceil() as i32),
+                colour,
+                alpha,
+                |x, y| in_polygon(x as f32 + 0.5, y as f32 + 0.5, &vertices),
+            );
+        }
+
+        image
+    }
+}
+
+/// Angled stripes whose edges wave and whose shades vary stripe by stripe.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Stripes;
+
+impl BackgroundGenerator for Stripes {
+    fn name(&self) -> &'static str {
+        BackgroundStyle::Stripes.name()
+    }
+
+    fn generate(&self, width: u32, height: u32, rng: &mut dyn RngCore) -> RgbaImage {
+        let colours = rng.gen_range(2..=4);
+        let palette: Vec<[f32; 3]> = (0..colours).map(|_| random_colour(rng, 40..=240)).collect();
+        let angle = rng.gen_range(0.0..PI);
+        let period: f32 = rng.gen_range(10.0..28.0);
+        let amplitude: f32 = rng.gen_range(2.0..8.0);
+        let frequency: f32 = rng.gen_range(0.01..0.05);
+        let phase = rng.gen_range(0.0..2.0 * PI);
+
+        // Stripe coordinates span at most the diagonal on either side of the origin.
+        let reach = width as f32 + height as f32 + amplitude;
+        let stripes = (2.0 * reach / period) as usize + 2;
+        let jitter: Vec<f32> = (0..stripes).map(|_| rng.gen_range(-18.0..18.0)).collect();
+
+        let (sin, cos) = angle.sin_cos();
+        fill(width, height, |x, y| {
+            let (x, y) = (x as f32, y as f32);
+            let across = x * cos + y * sin;
+            let along = y * cos - x * sin;
+            let t = across + amplitude * (along * frequency + phase).sin() + reach;
+            let index = ((t / period) as usize).min(stripes - 1);
+            let offset = jitter[index];
+            let [r, g, b] = palette[index % palette.len()];
+            to_pixel([r + offset, g + offset, b + offset])
+        })
+    }
+}
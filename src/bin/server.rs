@@ -1,11 +1,57 @@
-use std::sync::Arc;
+//! ```text
+//! server [--config FILE] [--print-config]
+//! ```
+//!
+//! `--config` (or `PUZZLE_CONFIG`) names a TOML or YAML file layered under the
+//! environment variables. `--print-config` prints the effective configuration and exits.
+//! SIGHUP or `POST /admin/reload` re-reads the same sources while running; SIGTERM or Ctrl-C
+//! shuts down gracefully, saving outstanding solutions and cached puzzles to `snapshot_file`
+//! if set.
 
-use slider_captcha_server::{config::AppConfig, server::run_server};
+use std::{env, path::PathBuf, process};
+
+use slider_captcha_server::{config::SharedConfig, server::run_server};
 use tracing_subscriber::{fmt, prelude::*, EnvFilter};
 
+struct Args {
+    config: Option<PathBuf>,
+    print_config: bool,
+}
+
+fn parse_args() -> Result<Args, String> {
+    let mut parsed = Args {
+        config: env::var_os("PUZZLE_CONFIG").map(PathBuf::from),
+        print_config: false,
+    };
+    let mut args = env::args().skip(1);
+    while let Some(flag) = args.next() {
+        match flag.as_str() {
+            "--config" => {
+                let path = args.next().ok_or("--config needs a value")?;
+                parsed.config = Some(PathBuf::from(path));
+            }
+            "--print-config" => parsed.print_config = true,
+            other => return Err(format!("unknown argument {other}")),
+        }
+    }
+    Ok(parsed)
+}
+
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
-    let config = Arc::new(AppConfig::from_env());
+    let args = parse_args().unwrap_or_else(|err| {
+        eprintln!("error: {err}");
+        process::exit(2);
+    });
+    let shared = SharedConfig::load_from(args.config).unwrap_or_else(|err| {
+        eprintln!("invalid configuration: {err}");
+        process::exit(2);
+    });
+    let config = shared.load();
+    if args.print_config {
+        print!("{}", config.to_toml());
+        return Ok(());
+    }
 
     tracing_subscriber::registry()
         .with(EnvFilter::new(config.log_level.clone()))
@@ -19,8 +65,9 @@ async fn main() -> std::io::Result<()> {
         generator_concurrency = %config.generator_concurrency,
         cache_prefill = %config.cache_prefill_per_size,
         cache_max = %config.cache_max_per_size,
+        sites = config.sites.len(),
         "Slider captcha server starting"
     );
 
-    run_server(config).await
+    run_server(shared).await
 }
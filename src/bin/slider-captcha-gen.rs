@@ -0,0 +1,260 @@
+//! Renders puzzles offline into a directory, with a JSONL manifest of their solutions.
+//!
+//! ```text
+//! slider-captcha-gen [--out DIR] [--count N] [--sizes WxH,...] [--seed S]
+//!                    [--type slider|click] [--pieces N] [--orientation horizontal|vertical]
+//!                    [--icons N] [--backgrounds noise:2,voronoi,...] [--difficulty LEVEL]
+//!                    [--format png|jpeg|webp]
+//! ```
+//!
+//! Every puzzle gets its own `<id>/` directory holding `background.<ext>` and either
+//! `piece.<ext>`, `piece-<n>.<ext>` or `hint.<ext>`. `manifest.jsonl` has one line per
+//! puzzle with its seed and answer. Seeds run consecutively from `--seed`, so the same
+//! flags always produce the same dataset.
+
+use std::{
+    env,
+    fs::{self, File},
+    io::{BufWriter, Write},
+    path::{Path, PathBuf},
+    process::ExitCode,
+};
+
+use slider_captcha_server::{
+    background::{try_parse_background_styles, BackgroundStyle, WeightedBackgrounds},
+    generator::{generate_puzzle, Challenge, ImageEncoding, PuzzleKey, RenderOptions},
+    render::{Difficulty, PieceStyle},
+    Orientation,
+};
+
+enum Kind {
+    Slider {
+        pieces: u8,
+        orientation: Orientation,
+    },
+    Click {
+        icons: u8,
+    },
+}
+
+struct Options {
+    out: PathBuf,
+    count: u64,
+    sizes: Vec<(u32, u32)>,
+    seed: u64,
+    kind: Kind,
+    backgrounds: Vec<(BackgroundStyle, u32)>,
+    difficulty: Difficulty,
+    encoding: ImageEncoding,
+}
+
+fn parse_size(raw: &str) -> Result<(u32, u32), String> {
+    let (width, height) = raw
+        .trim()
+        .split_once('x')
+        .ok_or_else(|| format!("invalid size {raw}, expected WxH"))?;
+    let width = width
+        .parse()
+        .map_err(|_| format!("invalid width in {raw}"))?;
+    let height = height
+        .parse()
+        .map_err(|_| format!("invalid height in {raw}"))?;
+    Ok((width, height))
+}
+
+fn parse_args() -> Result<Options, String> {
+    let mut options = Options {
+        out: PathBuf::from("dataset"),
+        count: 10,
+        sizes: vec![(500, 300)],
+        seed: rand::random::<u32>() as u64,
+        kind: Kind::Slider {
+            pieces: 1,
+            orientation: Orientation::Horizontal,
+        },
+        backgrounds: BackgroundStyle::ALL.map(|style| (style, 1)).to_vec(),
+        difficulty: Difficulty::Off,
+        encoding: ImageEncoding::Png,
+    };
+    let mut kind = "slider".to_string();
+    let (mut pieces, mut orientation, mut icons) = (1, Orientation::Horizontal, 4);
+
+    let mut args = env::args().skip(1);
+    while let Some(flag) = args.next() {
+        let mut value = || args.next().ok_or_else(|| format!("{flag} needs a value"));
+        match flag.as_str() {
+            "--out" => options.out = PathBuf::from(value()?),
+            "--count" => options.count = value()?.parse().map_err(|_| "invalid --count")?,
+            "--sizes" => {
+                options.sizes = value()?
+                    .split(',')
+                    .map(parse_size)
+                    .collect::<Result<_, _>>()?;
+            }
+            "--seed" => options.seed = value()?.parse().map_err(|_| "invalid --seed")?,
+            "--type" => kind = value()?,
+            "--pieces" => pieces = value()?.parse().map_err(|_| "invalid --pieces")?,
+            "--orientation" => {
+                orientation = match value()?.as_str() {
+                    "horizontal" => Orientation::Horizontal,
+                    "vertical" => Orientation::Vertical,
+                    other => return Err(format!("invalid --orientation {other}")),
+                };
+            }
+            "--icons" => icons = value()?.parse().map_err(|_| "invalid --icons")?,
+            "--backgrounds" => {
+                options.backgrounds = try_parse_background_styles(&value()?)
+                    .map_err(|err| format!("invalid --backgrounds: {err}"))?;
+            }
+            "--difficulty" => {
+                let raw = value()?;
+                options.difficulty =
+                    Difficulty::parse(&raw).ok_or_else(|| format!("unknown difficulty {raw}"))?;
+            }
+            "--format" => {
+                let raw = value()?;
+                options.encoding =
+                    ImageEncoding::parse(&raw).ok_or_else(|| format!("unknown format {raw}"))?;
+            }
+            other => return Err(format!("unknown argument {other}")),
+        }
+    }
+
+    options.kind = match kind.as_str() {
+        "slider" => Kind::Slider {
+            pieces,
+            orientation,
+        },
+        "click" => Kind::Click { icons },
+        other => return Err(format!("invalid --type {other}")),
+    };
+    Ok(options)
+}
+
+fn write_asset(dir: &Path, name: &str, extension: &str, bytes: &[u8]) -> Result<String, String> {
+    let file = format!("{name}.{extension}");
+    fs::write(dir.join(&file), bytes).map_err(|err| format!("{}: {err}", dir.display()))?;
+    Ok(file)
+}
+
+fn main() -> ExitCode {
+    match run() {
+        Ok(written) => {
+            println!("Wrote {written} puzzles");
+            ExitCode::SUCCESS
+        }
+        Err(err) => {
+            eprintln!("error: {err}");
+            ExitCode::from(2)
+        }
+    }
+}
+
+fn run() -> Result<u64, String> {
+    let options = parse_args()?;
+    let render = RenderOptions {
+        backgrounds: WeightedBackgrounds::from_styles(&options.backgrounds),
+        piece_style: PieceStyle::default(),
+        ..RenderOptions::default()
+    };
+
+    fs::create_dir_all(&options.out).map_err(|err| format!("{}: {err}", options.out.display()))?;
+    let manifest_path = options.out.join("manifest.jsonl");
+    let mut manifest = BufWriter::new(
+        File::create(&manifest_path)
+            .map_err(|err| format!("{}: {err}", manifest_path.display()))?,
+    );
+
+    let mut seed = options.seed;
+    let mut written = 0;
+    for &(width, height) in &options.sizes {
+        let key = match options.kind {
+            Kind::Slider {
+                pieces,
+                orientation,
+            } => PuzzleKey::slider_variant(width, height, pieces, orientation),
+            Kind::Click { icons } => PuzzleKey::click(width, height, icons),
+        }
+        .with_encoding(options.encoding)
+        .with_difficulty(options.difficulty);
+
+        for _ in 0..options.count {
+            let images = generate_puzzle(key, seed, &render).map_err(|err| err.to_string())?;
+            let id = format!("{width}x{height}-{seed}");
+            let dir = options.out.join(&id);
+            fs::create_dir_all(&dir).map_err(|err| format!("{}: {err}", dir.display()))?;
+
+            let background = write_asset(
+                &dir,
+                "background",
+                options.encoding.background().extension(),
+                &images.background,
+            )?;
+            let piece_extension = options.encoding.piece().extension();
+
+            let mut entry = serde_json::json!({
+                "id": id,
+                "seed": seed,
+                "width": width,
+                "height": height,
+                "difficulty": options.difficulty,
+                "background": format!("{id}/{background}"),
+            });
+            match &images.challenge {
+                Challenge::Slider {
+                    pieces,
+                    orientation,
+                    padding,
+                } => {
+                    let files = images
+                        .pieces
+                        .iter()
+                        .enumerate()
+                        .map(|(index, bytes)| {
+                            let name = if images.pieces.len() == 1 {
+                                "piece".to_string()
+                            } else {
+                                format!("piece-{index}")
+                            };
+                            let file = write_asset(&dir, &name, piece_extension, bytes)?;
+                            Ok(format!("{id}/{file}"))
+                        })
+                        .collect::<Result<Vec<_>, String>>()?;
+                    entry["type"] = serde_json::json!("slider");
+                    entry["orientation"] = serde_json::json!(orientation);
+                    entry["piece_padding"] = serde_json::json!(padding);
+                    entry["pieces"] = serde_json::json!(pieces
+                        .iter()
+                        .zip(files)
+                        .map(|(piece, file)| {
+                            serde_json::json!({ "image": file, "x": piece.x, "y": piece.y })
+                        })
+                        .collect::<Vec<_>>());
+                }
+                Challenge::Click {
+                    icons,
+                    points,
+                    tolerance,
+                } => {
+                    let hint = write_asset(&dir, "hint", piece_extension, &images.pieces[0])?;
+                    entry["type"] = serde_json::json!("click");
+                    entry["hint"] = serde_json::json!(format!("{id}/{hint}"));
+                    entry["icons"] =
+                        serde_json::json!(icons.iter().map(|icon| icon.name()).collect::<Vec<_>>());
+                    entry["points"] = serde_json::json!(points);
+                    entry["tolerance"] = serde_json::json!(tolerance);
+                }
+            }
+
+            writeln!(manifest, "{entry}")
+                .map_err(|err| format!("{}: {err}", manifest_path.display()))?;
+            seed += 1;
+            written += 1;
+        }
+    }
+
+    manifest
+        .flush()
+        .map_err(|err| format!("{}: {err}", manifest_path.display()))?;
+    Ok(written)
+}
@@ -0,0 +1,170 @@
+//! Runs the reference attacks against freshly generated puzzles and prints success rates.
+//!
+//! ```text
+//! solver [--samples N] [--size WxH] [--pieces N] [--orientation horizontal|vertical]
+//!        [--difficulty off,easy,...] [--backgrounds gradient,noise,...|mixed]
+//!        [--max-rate R]
+//! ```
+//!
+//! With `--max-rate`, the process exits with status 1 if any attack solves more than
+//! that fraction of puzzles in any configuration, so it can gate CI.
+
+use std::{env, process::ExitCode};
+
+use slider_captcha_server::{
+    background::{BackgroundStyle, WeightedBackgrounds},
+    render::{Difficulty, PieceStyle},
+    solver::{evaluate, standard_attacks, SolverConfig},
+    Orientation,
+};
+
+struct Options {
+    samples: u64,
+    width: u32,
+    height: u32,
+    pieces: usize,
+    orientation: Orientation,
+    difficulties: Vec<Difficulty>,
+    /// `None` mixes every style, as the server does by default.
+    backgrounds: Vec<Option<BackgroundStyle>>,
+    max_rate: Option<f64>,
+}
+
+fn parse_args() -> Result<Options, String> {
+    let mut options = Options {
+        samples: 100,
+        width: 500,
+        height: 300,
+        pieces: 1,
+        orientation: Orientation::Horizontal,
+        difficulties: Difficulty::ALL.to_vec(),
+        backgrounds: BackgroundStyle::ALL.map(Some).to_vec(),
+        max_rate: None,
+    };
+
+    let mut args = env::args().skip(1);
+    while let Some(flag) = args.next() {
+        let mut value = || args.next().ok_or_else(|| format!("{flag} needs a value"));
+        match flag.as_str() {
+            "--samples" => {
+                options.samples = value()?.parse().map_err(|_| "invalid --samples")?;
+            }
+            "--size" => {
+                let raw = value()?;
+                let (width, height) = raw
+                    .split_once('x')
+                    .ok_or_else(|| format!("invalid --size {raw}, expected WxH"))?;
+                options.width = width.parse().map_err(|_| "invalid --size width")?;
+                options.height = height.parse().map_err(|_| "invalid --size height")?;
+            }
+            "--pieces" => {
+                options.pieces = value()?.parse().map_err(|_| "invalid --pieces")?;
+            }
+            "--orientation" => {
+                options.orientation = match value()?.as_str() {
+                    "horizontal" => Orientation::Horizontal,
+                    "vertical" => Orientation::Vertical,
+                    other => return Err(format!("invalid --orientation {other}")),
+                };
+            }
+            "--difficulty" => {
+                options.difficulties = value()?
+                    .split(',')
+                    .map(|raw| {
+                        Difficulty::parse(raw).ok_or_else(|| format!("unknown difficulty {raw}"))
+                    })
+                    .collect::<Result<_, _>>()?;
+            }
+            "--backgrounds" => {
+                options.backgrounds = value()?
+                    .split(',')
+                    .map(|raw| match raw.trim() {
+                        "mixed" => Ok(None),
+                        name => BackgroundStyle::parse(name)
+                            .map(Some)
+                            .ok_or_else(|| format!("unknown background {name}")),
+                    })
+                    .collect::<Result<_, _>>()?;
+            }
+            "--max-rate" => {
+                options.max_rate = Some(value()?.parse().map_err(|_| "invalid --max-rate")?);
+            }
+            other => return Err(format!("unknown argument {other}")),
+        }
+    }
+
+    Ok(options)
+}
+
+fn main() -> ExitCode {
+    let options = match parse_args() {
+        Ok(options) => options,
+        Err(err) => {
+            eprintln!("error: {err}");
+            return ExitCode::from(2);
+        }
+    };
+
+    let attacks = standard_attacks();
+    let mut worst: Option<(String, &'static str, f64)> = None;
+
+    println!(
+        "{:<10} {:<10} {:<16} {:>12} {:>7}",
+        "background", "difficulty", "attack", "solved", "rate"
+    );
+    for &style in &options.backgrounds {
+        for &difficulty in &options.difficulties {
+            let backgrounds = match style {
+                Some(style) => WeightedBackgrounds::from_styles(&[(style, 1)]),
+                None => WeightedBackgrounds::default(),
+            };
+            let config = SolverConfig {
+                width: options.width,
+                height: options.height,
+                pieces: options.pieces,
+                orientation: options.orientation,
+                backgrounds,
+                piece_style: PieceStyle::default(),
+                difficulty,
+            };
+            let background = style.map_or("mixed", BackgroundStyle::name);
+
+            let reports = match evaluate(&config, 0..options.samples, &attacks) {
+                Ok(reports) => reports,
+                Err(err) => {
+                    eprintln!("error: {err}");
+                    return ExitCode::from(2);
+                }
+            };
+            for report in reports {
+                println!(
+                    "{:<10} {:<10} {:<16} {:>12} {:>6.1}%",
+                    background,
+                    difficulty.name(),
+                    report.attack,
+                    format!("{}/{}", report.solved, report.total),
+                    report.rate() * 100.0
+                );
+                if worst
+                    .as_ref()
+                    .is_none_or(|(_, _, rate)| report.rate() > *rate)
+                {
+                    let label = format!("{background}/{}", difficulty.name());
+                    worst = Some((label, report.attack, report.rate()));
+                }
+            }
+        }
+    }
+
+    match (options.max_rate, worst) {
+        (Some(max_rate), Some((config, attack, rate))) if rate > max_rate => {
+            eprintln!(
+                "{attack} solved {:.1}% of {config} puzzles, above the allowed {:.1}%",
+                rate * 100.0,
+                max_rate * 100.0
+            );
+            ExitCode::FAILURE
+        }
+        _ => ExitCode::SUCCESS,
+    }
+}
@@ -1,6 +1,9 @@
 use std::{
     collections::VecDeque,
-    sync::Arc,
+    sync::{
+        atomic::{AtomicU64, AtomicUsize, Ordering},
+        Arc,
+    },
     time::{Duration, Instant},
 };
 
@@ -17,8 +20,10 @@ pub struct ExpiringCache<K, V>
 where
     K: Eq + std::hash::Hash,
 {
-    ttl: Duration,
-    max_len: usize,
+    /// TTL in milliseconds; shared between clones so [`ExpiringCache::set_limits`] applies
+    /// to all of them.
+    ttl_ms: Arc<AtomicU64>,
+    max_len: Arc<AtomicUsize>,
     buckets: Arc<DashMap<K, VecDeque<CachedEntry<V>>>>,
 }
 
@@ -28,30 +33,80 @@ where
 {
     pub fn new(ttl: Duration, max_len: usize) -> Self {
         Self {
-            ttl,
-            max_len,
+            ttl_ms: Arc::new(AtomicU64::new(ttl.as_millis() as u64)),
+            max_len: Arc::new(AtomicUsize::new(max_len)),
             buckets: Arc::new(DashMap::new()),
         }
     }
 
+    /// Changes the TTL and per-bucket limit without dropping entries. Buckets above the
+    /// new limit shrink on their next insert; entries past the new TTL stop being served.
+    pub fn set_limits(&self, ttl: Duration, max_len: usize) {
+        self.ttl_ms.store(ttl.as_millis() as u64, Ordering::Relaxed);
+        self.max_len.store(max_len, Ordering::Relaxed);
+    }
+
+    pub fn ttl(&self) -> Duration {
+        Duration::from_millis(self.ttl_ms.load(Ordering::Relaxed))
+    }
+
+    pub fn max_len(&self) -> usize {
+        self.max_len.load(Ordering::Relaxed)
+    }
+
     pub fn insert(&self, key: K, value: V) {
-        let entry = CachedEntry {
-            value: Arc::new(value),
-            inserted_at: Instant::now(),
+        self.push(
+            key,
+            CachedEntry {
+                value: Arc::new(value),
+                inserted_at: Instant::now(),
+            },
+        );
+    }
+
+    /// Inserts an entry created `age` ago, so it expires when it would have originally.
+    /// Entries already past the TTL are dropped; returns whether the entry was kept.
+    pub fn insert_aged(&self, key: K, value: Arc<V>, age: Duration) -> bool {
+        let inserted_at = match Instant::now().checked_sub(age) {
+            Some(inserted_at) if age < self.ttl() => inserted_at,
+            _ => return false,
         };
+        self.push(key, CachedEntry { value, inserted_at });
+        true
+    }
+
+    /// Unexpired entries with their age, oldest first within each bucket.
+    pub fn entries(&self) -> Vec<(K, Arc<V>, Duration)> {
+        let ttl = self.ttl();
+        self.buckets
+            .iter()
+            .flat_map(|bucket| {
+                let key = bucket.key().clone();
+                bucket
+                    .iter()
+                    .map(|entry| (entry.inserted_at.elapsed(), entry.value.clone()))
+                    .filter(|(age, _)| *age < ttl)
+                    .map(|(age, value)| (key.clone(), value, age))
+                    .collect::<Vec<_>>()
+            })
+            .collect()
+    }
 
-        let mut bucket = self.buckets.entry(key).or_insert_with(VecDeque::new);
+    fn push(&self, key: K, entry: CachedEntry<V>) {
+        let max_len = self.max_len();
+        let mut bucket = self.buckets.entry(key).or_default();
 
         bucket.push_back(entry);
-        while bucket.len() > self.max_len {
+        while bucket.len() > max_len {
             bucket.pop_front();
         }
     }
 
     pub fn pop(&self, key: &K) -> Option<Arc<V>> {
+        let ttl = self.ttl();
         if let Some(mut bucket) = self.buckets.get_mut(key) {
             while let Some(entry) = bucket.pop_back() {
-                if entry.inserted_at.elapsed() < self.ttl {
+                if entry.inserted_at.elapsed() < ttl {
                     return Some(entry.value);
                 }
             }
@@ -60,37 +115,62 @@ where
     }
 
     pub fn len_for(&self, key: &K) -> usize {
+        let ttl = self.ttl();
         self.buckets
             .get(key)
             .map(|bucket| {
                 bucket
                     .iter()
-                    .filter(|entry| entry.inserted_at.elapsed() < self.ttl)
+                    .filter(|entry| entry.inserted_at.elapsed() < ttl)
                     .count()
             })
             .unwrap_or(0)
     }
 
+    /// Unexpired entries per bucket.
+    pub fn bucket_lens(&self) -> Vec<(K, usize)> {
+        let ttl = self.ttl();
+        self.buckets
+            .iter()
+            .map(|bucket| {
+                let live = bucket
+                    .iter()
+                    .filter(|entry| entry.inserted_at.elapsed() < ttl)
+                    .count();
+                (bucket.key().clone(), live)
+            })
+            .collect()
+    }
+
+    /// Drops a whole bucket; returns how many entries it held.
+    pub fn remove_bucket(&self, key: &K) -> usize {
+        self.buckets
+            .remove(key)
+            .map_or(0, |(_, bucket)| bucket.len())
+    }
+
     pub fn total_len(&self) -> usize {
+        let ttl = self.ttl();
         self.buckets
             .iter()
             .map(|bucket| {
                 bucket
                     .iter()
-                    .filter(|entry| entry.inserted_at.elapsed() < self.ttl)
+                    .filter(|entry| entry.inserted_at.elapsed() < ttl)
                     .count()
             })
             .sum()
     }
 
     pub fn clean_expired(&self) -> (usize, usize) {
+        let ttl = self.ttl();
         let mut removed = 0;
         let mut remaining = 0;
 
         for mut bucket in self.buckets.iter_mut() {
             let vec = bucket.value_mut();
             let before = vec.len();
-            vec.retain(|entry| entry.inserted_at.elapsed() < self.ttl);
+            vec.retain(|entry| entry.inserted_at.elapsed() < ttl);
             removed += before - vec.len();
             remaining += vec.len();
         }
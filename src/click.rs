@@ -0,0 +1,283 @@
+use image::{DynamicImage, GenericImage, GenericImageView, Rgba, RgbaImage};
+use rand::{seq::SliceRandom, Rng};
+
+use crate::{
+    background::{BackgroundGenerator, Gradient},
+    error::PuzzleError,
+    puzzle::validate_dimensions,
+};
+
+pub const MIN_ICONS: usize = 2;
+pub const MAX_ICONS: usize = 6;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub enum Icon {
+    Circle,
+    Square,
+    Triangle,
+    Diamond,
+    Star,
+    Cross,
+    Ring,
+    Hexagon,
+}
+
+impl Icon {
+    pub const ALL: [Icon; 8] = [
+        Icon::Circle,
+        Icon::Square,
+        Icon::Triangle,
+        Icon::Diamond,
+        Icon::Star,
+        Icon::Cross,
+        Icon::Ring,
+        Icon::Hexagon,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Icon::Circle => "circle",
+            Icon::Square => "square",
+            Icon::Triangle => "triangle",
+            Icon::Diamond => "diamond",
+            Icon::Star => "star",
+            Icon::Cross => "cross",
+            Icon::Ring => "ring",
+            Icon::Hexagon => "hexagon",
+        }
+    }
+
+    /// Hit-test in icon-local coordinates, where the icon fills the unit disc.
+    fn contains(&self, u: f32, v: f32) -> bool {
+        match self {
+            Icon::Circle => u * u + v * v <= 1.0,
+            Icon::Square => u.abs() <= 0.75 && v.abs() <= 0.75,
+            Icon::Diamond => u.abs() + v.abs() <= 1.0,
+            Icon::Cross => {
+                (u.abs() <= 0.3 && v.abs() <= 0.95) || (v.abs() <= 0.3 && u.abs() <= 0.95)
+            }
+            Icon::Ring => {
+                let d = u * u + v * v;
+                (0.3..=1.0).contains(&d)
+            }
+            Icon::Triangle => in_polygon(u, v, &regular_polygon(3, 1.0, 1.0)),
+            Icon::Hexagon => in_polygon(u, v, &regular_polygon(6, 1.0, 1.0)),
+            Icon::Star => in_polygon(u, v, &regular_polygon(5, 1.0, 0.45)),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ClickPuzzle {
+    pub image: DynamicImage,
+    /// Icons drawn in the order they must be clicked, used as the on-screen prompt.
+    pub hint: DynamicImage,
+    pub icons: Vec<Icon>,
+    /// Icon centres relative to the image size, in click order.
+    pub points: Vec<(f64, f64)>,
+    /// Accepted distance from each centre, relative to width and height.
+    pub tolerance: (f64, f64),
+}
+
+impl ClickPuzzle {
+    pub fn from_dimensions(
+        width: u32,
+        height: u32,
+        count: usize,
+    ) -> Result<ClickPuzzle, PuzzleError> {
+        Self::from_dimensions_with_rng(width, height, count, &mut rand::thread_rng())
+    }
+
+    pub fn from_dimensions_with_rng<R: Rng + ?Sized>(
+        width: u32,
+        height: u32,
+        count: usize,
+        rng: &mut R,
+    ) -> Result<ClickPuzzle, PuzzleError> {
+        validate_dimensions(width, height)?;
+        validate_icon_count(count)?;
+
+        let background = Gradient.generate(width, height, &mut &mut *rng);
+        Self::from_background(background, count, rng)
+    }
+
+    /// Draws `count` icons onto an already rendered background.
+    pub fn from_background<R: Rng + ?Sized>(
+        background: RgbaImage,
+        count: usize,
+        rng: &mut R,
+    ) -> Result<ClickPuzzle, PuzzleError> {
+        let (width, height) = background.dimensions();
+        validate_dimensions(width, height)?;
+        validate_icon_count(count)?;
+
+        let mut image = DynamicImage::ImageRgba8(background);
+
+        let radius = (width.min(height) / 12).max(8);
+        let icons: Vec<Icon> = Icon::ALL.choose_multiple(rng, count).copied().collect();
+
+        let mut centres: Vec<(u32, u32)> = Vec::with_capacity(count);
+        for _ in 0..count {
+            let centre = (0..200)
+                .map(|_| {
+                    (
+                        rng.gen_range(radius..width.saturating_sub(radius).max(radius + 1)),
+                        rng.gen_range(radius..height.saturating_sub(radius).max(radius + 1)),
+                    )
+                })
+                .find(|&(x, y)| {
+                    centres.iter().all(|&(ox, oy)| {
+                        let dx = x as i64 - ox as i64;
+                        let dy = y as i64 - oy as i64;
+                        let min_gap = 2 * radius as i64 + 4;
+                        dx * dx + dy * dy >= min_gap * min_gap
+                    })
+                })
+                .ok_or_else(|| {
+                    PuzzleError::InvalidLayout(format!(
+                        "cannot place {count} icons on a {width}x{height} image"
+                    ))
+                })?;
+            centres.push(centre);
+        }
+
+        for (icon, &(cx, cy)) in icons.iter().zip(&centres) {
+            let fill = Rgba([
+                rng.gen_range(0..=255),
+                rng.gen_range(0..=255),
+                rng.gen_range(0..=255),
+                230,
+            ]);
+            let rotation = match icon {
+                Icon::Circle | Icon::Ring => 0.0,
+                _ => rng.gen_range(-0.5..0.5),
+            };
+            draw_icon(&mut image, *icon, cx, cy, radius, rotation, fill);
+        }
+
+        let cell = 2 * radius + 8;
+        let mut hint = DynamicImage::new_rgba8(cell * count as u32, cell);
+        for (index, icon) in icons.iter().enumerate() {
+            let cx = cell * index as u32 + cell / 2;
+            draw_icon(
+                &mut hint,
+                *icon,
+                cx,
+                cell / 2,
+                radius,
+                0.0,
+                Rgba([60, 60, 60, 255]),
+            );
+        }
+
+        Ok(ClickPuzzle {
+            image,
+            hint,
+            icons,
+            points: centres
+                .iter()
+                .map(|&(x, y)| (x as f64 / width as f64, y as f64 / height as f64))
+                .collect(),
+            tolerance: (radius as f64 / width as f64, radius as f64 / height as f64),
+        })
+    }
+}
+
+fn validate_icon_count(count: usize) -> Result<(), PuzzleError> {
+    if (MIN_ICONS..=MAX_ICONS).contains(&count) {
+        Ok(())
+    } else {
+        Err(PuzzleError::InvalidLayout(format!(
+            "icon count must be between {MIN_ICONS} and {MAX_ICONS}, got {count}"
+        )))
+    }
+}
+
+/// Every submitted point must land on the matching icon, in the same order.
+pub fn verify_clicks(
+    solution: &[(f64, f64)],
+    submission: &[(f64, f64)],
+    tolerance: (f64, f64),
+) -> bool {
+    solution.len() == submission.len()
+        && solution.iter().zip(submission).all(|(expected, actual)| {
+            let dx = (expected.0 - actual.0) / tolerance.0;
+            let dy = (expected.1 - actual.1) / tolerance.1;
+            dx * dx + dy * dy <= 1.0
+        })
+}
+
+fn draw_icon(
+    image: &mut DynamicImage,
+    icon: Icon,
+    cx: u32,
+    cy: u32,
+    radius: u32,
+    rotation: f32,
+    fill: Rgba<u8>,
+) {
+    let (width, height) = image.dimensions();
+    let outline = Rgba([255, 255, 255, 255]);
+    let r = radius as f32;
+    let inner = (r - 2.0).max(1.0);
+    let (sin, cos) = rotation.sin_cos();
+
+    let x0 = cx.saturating_sub(radius);
+    let y0 = cy.saturating_sub(radius);
+    let x1 = (cx + radius).min(width.saturating_sub(1));
+    let y1 = (cy + radius).min(height.saturating_sub(1));
+
+    for y in y0..=y1 {
+        for x in x0..=x1 {
+            let dx = x as f32 - cx as f32;
+            let dy = y as f32 - cy as f32;
+            let rx = dx * cos - dy * sin;
+            let ry = dx * sin + dy * cos;
+
+            let colour = if icon.contains(rx / inner, ry / inner) {
+                fill
+            } else if icon.contains(rx / r, ry / r) {
+                outline
+            } else {
+                continue;
+            };
+
+            let old = image.get_pixel(x, y);
+            let blend = colour[3] as f32 / 255.0;
+            let mix = |c: usize| (colour[c] as f32 * blend + old[c] as f32 * (1.0 - blend)) as u8;
+            let alpha = old[3].max(colour[3]);
+            image.put_pixel(x, y, Rgba([mix(0), mix(1), mix(2), alpha]));
+        }
+    }
+}
+
+/// Vertices of a regular polygon (or a star when `inner < outer`), pointing up.
+fn regular_polygon(points: usize, outer: f32, inner: f32) -> Vec<(f32, f32)> {
+    let steps = if inner < outer { points * 2 } else { points };
+    (0..steps)
+        .map(|i| {
+            let radius = if i % 2 == 1 && inner < outer {
+                inner
+            } else {
+                outer
+            };
+            let angle =
+                std::f32::consts::TAU * i as f32 / steps as f32 - std::f32::consts::FRAC_PI_2;
+            (radius * angle.cos(), radius * angle.sin())
+        })
+        .collect()
+}
+
+pub(crate) fn in_polygon(u: f32, v: f32, vertices: &[(f32, f32)]) -> bool {
+    let mut inside = false;
+    let mut j = vertices.len() - 1;
+    for i in 0..vertices.len() {
+        let (xi, yi) = vertices[i];
+        let (xj, yj) = vertices[j];
+        if (yi > v) != (yj > v) && u < (xj - xi) * (v - yi) / (yj - yi) + xi {
+            inside = !inside;
+        }
+        j = i;
+    }
+    inside
+}
@@ -0,0 +1,493 @@
+//! Collects raw setting values from the config file and the environment, then parses
+//! and validates them into an [`AppConfig`].
+
+use std::{
+    collections::HashMap,
+    env, fs,
+    path::{Path, PathBuf},
+    str::FromStr,
+    time::Duration,
+};
+
+use super::{AppConfig, ConfigError, ImageDelivery};
+use crate::{
+    background::{try_parse_background_styles, BackgroundStyle},
+    generator::ImageEncoding,
+    puzzle::validate_dimensions,
+    render::{parse_colour, Difficulty, PieceStyle},
+    site::{SiteConfig, SiteRegistry},
+};
+
+/// Config file keys and the environment variables that override them.
+const KEYS: &[(&str, &str)] = &[
+    ("host", "SERVER_HOST"),
+    ("port", "SERVER_PORT"),
+    ("workers", "SERVER_WORKERS"),
+    ("solution_ttl_secs", "PUZZLE_SOLUTION_TTL_SECS"),
+    ("cache_ttl_secs", "PUZZLE_CACHE_TTL_SECS"),
+    ("cache_prefill", "PUZZLE_CACHE_PREFILL"),
+    ("cache_max", "PUZZLE_CACHE_MAX"),
+    ("generator_concurrency", "PUZZLE_GENERATOR_CONCURRENCY"),
+    ("cleanup_interval_secs", "CLEANUP_INTERVAL_SECS"),
+    ("prefill_dimensions", "PUZZLE_PREFILL_DIMENSIONS"),
+    ("log_level", "RUST_LOG"),
+    ("immediate_cache_cleanup", "IMMEDIATE_CACHE_CLEANUP"),
+    ("log_seeds", "PUZZLE_LOG_SEEDS"),
+    ("image_delivery", "PUZZLE_IMAGE_DELIVERY"),
+    ("image_format", "PUZZLE_IMAGE_FORMAT"),
+    ("image_negotiate", "PUZZLE_IMAGE_NEGOTIATE"),
+    ("image_quality", "PUZZLE_IMAGE_QUALITY"),
+    ("backgrounds", "PUZZLE_BACKGROUNDS"),
+    ("difficulty", "PUZZLE_DIFFICULTY"),
+    ("piece_outline_color", "PUZZLE_PIECE_OUTLINE_COLOR"),
+    ("piece_outline_width", "PUZZLE_PIECE_OUTLINE_WIDTH"),
+    ("piece_glow_color", "PUZZLE_PIECE_GLOW_COLOR"),
+    ("piece_glow", "PUZZLE_PIECE_GLOW"),
+    ("piece_shadow_color", "PUZZLE_PIECE_SHADOW_COLOR"),
+    ("piece_shadow", "PUZZLE_PIECE_SHADOW"),
+    ("hole_shade", "PUZZLE_HOLE_SHADE"),
+    ("sites_file", "PUZZLE_SITES_FILE"),
+    ("require_site_key", "PUZZLE_REQUIRE_SITE_KEY"),
+    ("admin_token", "PUZZLE_ADMIN_TOKEN"),
+    ("snapshot_file", "PUZZLE_SNAPSHOT_FILE"),
+    ("snapshot_interval_secs", "PUZZLE_SNAPSHOT_INTERVAL_SECS"),
+    ("ready_min_prefill", "PUZZLE_READY_MIN_PREFILL"),
+    ("max_attempts", "PUZZLE_MAX_ATTEMPTS"),
+    ("rate_limit_per_minute", "PUZZLE_RATE_LIMIT_PER_MINUTE"),
+];
+
+/// A raw value and where it came from, for error messages.
+struct Raw {
+    value: String,
+    source: String,
+}
+
+#[derive(Default)]
+pub(super) struct Settings {
+    values: HashMap<&'static str, Raw>,
+    /// Inline `sites` from the config file, with the file name.
+    sites: Option<(Vec<SiteConfig>, String)>,
+}
+
+fn error(source: impl Into<String>, message: impl Into<String>) -> ConfigError {
+    ConfigError {
+        source: source.into(),
+        message: message.into(),
+    }
+}
+
+impl Settings {
+    pub(super) fn read_file(&mut self, path: &Path) -> Result<(), ConfigError> {
+        let name = path.display().to_string();
+        let raw = fs::read_to_string(path).map_err(|err| error(&name, err.to_string()))?;
+        let yaml = matches!(
+            path.extension().and_then(|ext| ext.to_str()),
+            Some("yaml" | "yml")
+        );
+        let document: serde_json::Value = if yaml {
+            serde_yaml::from_str(&raw).map_err(|err| error(&name, err.to_string()))?
+        } else {
+            toml::from_str(&raw).map_err(|err| error(&name, err.to_string()))?
+        };
+        let serde_json::Value::Object(entries) = document else {
+            return Err(error(&name, "expected a table of settings"));
+        };
+
+        for (key, value) in entries {
+            let source = format!("{name}: {key}");
+            if key == "sites" {
+                let sites =
+                    serde_json::from_value(value).map_err(|err| error(&source, err.to_string()))?;
+                self.sites = Some((sites, name.clone()));
+                continue;
+            }
+            let Some(&(key, _)) = KEYS.iter().find(|(known, _)| *known == key) else {
+                return Err(error(source, "unknown setting"));
+            };
+            let value = scalar(&value)
+                .or_else(|| {
+                    let items = value.as_array()?;
+                    items
+                        .iter()
+                        .map(scalar)
+                        .collect::<Option<Vec<_>>>()
+                        .map(|items| items.join(","))
+                })
+                .ok_or_else(|| {
+                    error(
+                        &source,
+                        "expected a string, number, boolean or list of them",
+                    )
+                })?;
+            self.values.insert(key, Raw { value, source });
+        }
+        Ok(())
+    }
+
+    pub(super) fn read_env(&mut self) -> Result<(), ConfigError> {
+        for &(key, name) in KEYS {
+            match env::var(name) {
+                Ok(value) => {
+                    // A sites file from the environment replaces the file's inline sites.
+                    if key == "sites_file" {
+                        self.sites = None;
+                    }
+                    let source = name.to_string();
+                    self.values.insert(key, Raw { value, source });
+                }
+                Err(env::VarError::NotPresent) => {}
+                Err(err) => return Err(error(name, err.to_string())),
+            }
+        }
+        Ok(())
+    }
+
+    /// Parses `key` with `parse`, or returns `default` when it is unset.
+    fn get<T>(
+        &self,
+        key: &str,
+        default: T,
+        parse: impl FnOnce(&str) -> Result<T, String>,
+    ) -> Result<T, ConfigError> {
+        match self.values.get(key) {
+            Some(raw) => parse(raw.value.trim()).map_err(|message| error(&raw.source, message)),
+            None => Ok(default),
+        }
+    }
+
+    fn parsed<T: FromStr>(&self, key: &str, default: T) -> Result<T, ConfigError> {
+        self.get(key, default, |raw| {
+            raw.parse()
+                .map_err(|_| format!("invalid value {raw:?} for {}", std::any::type_name::<T>()))
+        })
+    }
+
+    fn positive(&self, key: &str, default: usize) -> Result<usize, ConfigError> {
+        self.get(key, default, |raw| {
+            raw.parse()
+                .ok()
+                .filter(|value| *value > 0)
+                .ok_or_else(|| format!("expected a positive integer, got {raw:?}"))
+        })
+    }
+
+    fn secs(&self, key: &str, default: u64) -> Result<Duration, ConfigError> {
+        self.get(key, Duration::from_secs(default), |raw| {
+            raw.parse()
+                .ok()
+                .filter(|secs| *secs > 0)
+                .map(Duration::from_secs)
+                .ok_or_else(|| format!("expected a positive number of seconds, got {raw:?}"))
+        })
+    }
+
+    fn boolean(&self, key: &str, default: bool) -> Result<bool, ConfigError> {
+        self.get(key, default, |raw| {
+            raw.parse()
+                .map_err(|_| format!("expected true or false, got {raw:?}"))
+        })
+    }
+
+    fn colour(&self, key: &str, default: [u8; 3]) -> Result<[u8; 3], ConfigError> {
+        self.get(key, default, |raw| {
+            parse_colour(raw).ok_or_else(|| format!("expected a #rrggbb colour, got {raw:?}"))
+        })
+    }
+
+    fn fraction(&self, key: &str, default: f32) -> Result<f32, ConfigError> {
+        self.get(key, default, |raw| {
+            raw.parse()
+                .ok()
+                .filter(|value| (0.0..=1.0).contains(value))
+                .ok_or_else(|| format!("expected a number between 0 and 1, got {raw:?}"))
+        })
+    }
+
+    /// Where `key` was set, for cross-field errors.
+    fn source(&self, key: &str) -> String {
+        self.values
+            .get(key)
+            .map_or_else(|| key.to_string(), |raw| raw.source.clone())
+    }
+
+    pub(super) fn build(self) -> Result<AppConfig, ConfigError> {
+        let defaults = PieceStyle::default();
+        let piece_style = PieceStyle {
+            outline_colour: self.colour("piece_outline_color", defaults.outline_colour)?,
+            outline_width: self.parsed("piece_outline_width", defaults.outline_width)?,
+            glow_colour: self.colour("piece_glow_color", defaults.glow_colour)?,
+            glow_strength: self.fraction("piece_glow", defaults.glow_strength)?,
+            shadow_colour: self.colour("piece_shadow_color", defaults.shadow_colour)?,
+            shadow_strength: self.fraction("piece_shadow", defaults.shadow_strength)?,
+            hole_shade: self.fraction("hole_shade", defaults.hole_shade)?,
+        };
+
+        let config = AppConfig {
+            host: self.get("host", "0.0.0.0".to_string(), |raw| Ok(raw.to_string()))?,
+            port: self.parsed("port", 8080)?,
+            workers: self.positive("workers", num_cpus::get())?,
+            solution_ttl: self.secs("solution_ttl_secs", 600)?,
+            puzzle_ttl: self.secs("cache_ttl_secs", 300)?,
+            cache_prefill_per_size: self.positive("cache_prefill", 8)?,
+            cache_max_per_size: self.positive("cache_max", 32)?,
+            generator_concurrency: self
+                .positive("generator_concurrency", num_cpus::get().max(2))?,
+            cleanup_interval: self.secs("cleanup_interval_secs", 300)?,
+            prefill_dimensions: self.get(
+                "prefill_dimensions",
+                vec![(500, 300)],
+                parse_dimensions,
+            )?,
+            log_level: self.get("log_level", "info".to_string(), |raw| Ok(raw.to_string()))?,
+            immediate_cache_cleanup: self.boolean("immediate_cache_cleanup", true)?,
+            log_puzzle_seeds: self.boolean("log_seeds", false)?,
+            image_delivery: self.get("image_delivery", ImageDelivery::Inline, |raw| {
+                ImageDelivery::parse(raw)
+                    .ok_or_else(|| format!("expected inline or url, got {raw:?}"))
+            })?,
+            image_encoding: self.get("image_format", ImageEncoding::Png, |raw| {
+                ImageEncoding::parse(raw)
+                    .ok_or_else(|| format!("expected png, jpeg or webp, got {raw:?}"))
+            })?,
+            negotiate_image_encoding: self.boolean("image_negotiate", false)?,
+            image_quality: self.get("image_quality", 80, |raw| {
+                raw.parse()
+                    .ok()
+                    .filter(|value| (1..=100).contains(value))
+                    .ok_or_else(|| format!("expected 1-100, got {raw:?}"))
+            })?,
+            background_styles: self.get(
+                "backgrounds",
+                BackgroundStyle::ALL.map(|style| (style, 1)).to_vec(),
+                try_parse_background_styles,
+            )?,
+            piece_style,
+            difficulty: self.get("difficulty", Difficulty::Off, |raw| {
+                Difficulty::parse(raw)
+                    .ok_or_else(|| format!("expected off, easy, normal or hard, got {raw:?}"))
+            })?,
+            sites: self.sites()?,
+            require_site_key: self.boolean("require_site_key", false)?,
+            admin_token: self.get("admin_token", None, |raw| {
+                if raw.len() < 16 {
+                    Err("must be at least 16 characters".to_string())
+                } else {
+                    Ok(Some(raw.to_string()))
+                }
+            })?,
+            snapshot_file: self.get("snapshot_file", None, |raw| {
+                if raw.is_empty() {
+                    Err("must not be empty".to_string())
+                } else {
+                    Ok(Some(PathBuf::from(raw)))
+                }
+            })?,
+            ready_min_prefill: self.parsed("ready_min_prefill", 1)?,
+            max_attempts: self.get("max_attempts", 5, |raw| {
+                raw.parse()
+                    .ok()
+                    .filter(|attempts| *attempts > 0)
+                    .ok_or_else(|| format!("expected a positive integer, got {raw:?}"))
+            })?,
+            rate_limit_per_minute: self.parsed("rate_limit_per_minute", 0)?,
+            snapshot_interval: match self.values.get("snapshot_interval_secs") {
+                Some(_) => Some(self.secs("snapshot_interval_secs", 0)?),
+                None => None,
+            },
+        };
+
+        if config.cache_prefill_per_size > config.cache_max_per_size {
+            return Err(error(
+                self.source("cache_prefill"),
+                format!(
+                    "prefill of {} exceeds cache_max of {}",
+                    config.cache_prefill_per_size, config.cache_max_per_size
+                ),
+            ));
+        }
+        if config.ready_min_prefill > config.cache_prefill_per_size {
+            return Err(error(
+                self.source("ready_min_prefill"),
+                format!(
+                    "{} exceeds cache_prefill of {}, so the server would never be ready",
+                    config.ready_min_prefill, config.cache_prefill_per_size
+                ),
+            ));
+        }
+        if config.snapshot_interval.is_some() && config.snapshot_file.is_none() {
+            return Err(error(
+                self.source("snapshot_interval_secs"),
+                "needs snapshot_file to write to",
+            ));
+        }
+        if config.require_site_key && config.sites.is_empty() {
+            return Err(error(
+                self.source("require_site_key"),
+                "no sites are configured, so every request would be rejected",
+            ));
+        }
+        Ok(config)
+    }
+
+    fn sites(&self) -> Result<SiteRegistry, ConfigError> {
+        match (&self.sites, self.values.get("sites_file")) {
+            (Some(_), Some(file)) => Err(error(
+                &file.source,
+                "sites are also listed in the config file; use one or the other",
+            )),
+            (Some((sites, name)), None) => SiteRegistry::new(sites.clone())
+                .map_err(|err| error(format!("{name}: sites"), err.to_string())),
+            (None, Some(file)) => SiteRegistry::from_json_file(file.value.trim())
+                .map_err(|err| error(&file.source, err.to_string())),
+            (None, None) => Ok(SiteRegistry::default()),
+        }
+    }
+}
+
+fn scalar(value: &serde_json::Value) -> Option<String> {
+    match value {
+        serde_json::Value::String(value) => Some(value.clone()),
+        serde_json::Value::Number(value) => Some(value.to_string()),
+        serde_json::Value::Bool(value) => Some(value.to_string()),
+        _ => None,
+    }
+}
+
+/// Parses `500x300,320x200`; an empty string disables prefilling.
+fn parse_dimensions(raw: &str) -> Result<Vec<(u32, u32)>, String> {
+    raw.split(',')
+        .map(str::trim)
+        .filter(|part| !part.is_empty())
+        .map(|part| {
+            let (width, height) = part
+                .split_once('x')
+                .and_then(|(width, height)| {
+                    Some((width.trim().parse().ok()?, height.trim().parse().ok()?))
+                })
+                .ok_or_else(|| format!("invalid size {part:?}, expected WxH"))?;
+            validate_dimensions(width, height).map_err(|err| err.to_string())?;
+            Ok((width, height))
+        })
+        .collect()
+}
+
+fn colour_value(colour: [u8; 3]) -> toml::Value {
+    let [r, g, b] = colour;
+    toml::Value::String(format!("#{r:02x}{g:02x}{b:02x}"))
+}
+
+/// Renders `config` with the same keys [`Settings::read_file`] accepts.
+pub(super) fn to_toml(config: &AppConfig) -> String {
+    use toml::Value;
+
+    let int = |value: u64| Value::Integer(value as i64);
+    // Through the shortest decimal form, so 0.35f32 prints as 0.35.
+    let float = |value: f32| Value::Float(value.to_string().parse().unwrap_or_default());
+    let string = |value: &str| Value::String(value.to_string());
+    let list = |items: Vec<String>| Value::Array(items.into_iter().map(Value::String).collect());
+    let style = &config.piece_style;
+
+    let values = [
+        ("host", string(&config.host)),
+        ("port", int(config.port.into())),
+        ("workers", int(config.workers as u64)),
+        ("solution_ttl_secs", int(config.solution_ttl.as_secs())),
+        ("cache_ttl_secs", int(config.puzzle_ttl.as_secs())),
+        ("cache_prefill", int(config.cache_prefill_per_size as u64)),
+        ("cache_max", int(config.cache_max_per_size as u64)),
+        ("ready_min_prefill", int(config.ready_min_prefill as u64)),
+        ("max_attempts", int(config.max_attempts.into())),
+        (
+            "rate_limit_per_minute",
+            int(config.rate_limit_per_minute.into()),
+        ),
+        (
+            "generator_concurrency",
+            int(config.generator_concurrency as u64),
+        ),
+        (
+            "cleanup_interval_secs",
+            int(config.cleanup_interval.as_secs()),
+        ),
+        (
+            "prefill_dimensions",
+            list(
+                config
+                    .prefill_dimensions
+                    .iter()
+                    .map(|(width, height)| format!("{width}x{height}"))
+                    .collect(),
+            ),
+        ),
+        ("log_level", string(&config.log_level)),
+        (
+            "immediate_cache_cleanup",
+            Value::Boolean(config.immediate_cache_cleanup),
+        ),
+        ("log_seeds", Value::Boolean(config.log_puzzle_seeds)),
+        ("image_delivery", string(config.image_delivery.name())),
+        ("image_format", string(config.image_encoding.name())),
+        (
+            "image_negotiate",
+            Value::Boolean(config.negotiate_image_encoding),
+        ),
+        ("image_quality", int(config.image_quality.into())),
+        (
+            "backgrounds",
+            list(
+                config
+                    .background_styles
+                    .iter()
+                    .map(|(style, weight)| format!("{}:{weight}", style.name()))
+                    .collect(),
+            ),
+        ),
+        ("difficulty", string(config.difficulty.name())),
+        ("piece_outline_color", colour_value(style.outline_colour)),
+        ("piece_outline_width", int(style.outline_width.into())),
+        ("piece_glow_color", colour_value(style.glow_colour)),
+        ("piece_glow", float(style.glow_strength)),
+        ("piece_shadow_color", colour_value(style.shadow_colour)),
+        ("piece_shadow", float(style.shadow_strength)),
+        ("hole_shade", float(style.hole_shade)),
+        ("require_site_key", Value::Boolean(config.require_site_key)),
+    ];
+    let snapshot_file = config
+        .snapshot_file
+        .as_ref()
+        .map(|path| ("snapshot_file", string(&path.to_string_lossy())));
+    let snapshot_interval = config
+        .snapshot_interval
+        .map(|interval| ("snapshot_interval_secs", int(interval.as_secs())));
+    let admin_token = config
+        .admin_token
+        .as_ref()
+        .map(|_| ("admin_token", string("<redacted:admin_token>")));
+
+    let mut output: String = values
+        .iter()
+        .chain(&admin_token)
+        .chain(&snapshot_file)
+        .chain(&snapshot_interval)
+        .map(|(key, value)| format!("{key} = {value}\n"))
+        .collect();
+
+    #[derive(serde::Serialize)]
+    struct Sites<'a> {
+        sites: [&'a SiteConfig; 1],
+    }
+    for site in config.sites.sites() {
+        let table =
+            toml::to_string(&Sites { sites: [site] }).expect("site settings serialize to TOML");
+        // Registries reject shared secrets, so each site gets its own placeholder and the
+        // output still loads.
+        let placeholder = string(&format!("<redacted:{}>", site.site_key));
+        output.push('\n');
+        output.push_str(&table.replacen(
+            "secret_key = \"<redacted>\"",
+            &format!("secret_key = {placeholder}"),
+            1,
+        ));
+    }
+    output
+}
@@ -0,0 +1,210 @@
+//! Server configuration.
+//!
+//! Settings are layered: built-in defaults, then an optional TOML or YAML file, then
+//! environment variables. Every value is validated, so a typo fails startup with a
+//! message naming the offending variable or file key instead of falling back silently.
+
+use std::{
+    fmt,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::Duration,
+};
+
+use parking_lot::RwLock;
+
+use crate::{
+    background::BackgroundStyle,
+    generator::ImageEncoding,
+    render::{Difficulty, PieceStyle},
+    site::SiteRegistry,
+};
+
+mod layer;
+
+use layer::Settings;
+
+/// How `/puzzle` hands the images to the client.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ImageDelivery {
+    /// Base64 PNGs embedded in the JSON response.
+    #[default]
+    Inline,
+    /// Short-lived `/puzzle/{id}/*.png` URLs serving raw bytes.
+    Url,
+}
+
+impl ImageDelivery {
+    pub fn name(self) -> &'static str {
+        match self {
+            ImageDelivery::Inline => "inline",
+            ImageDelivery::Url => "url",
+        }
+    }
+
+    pub fn parse(raw: &str) -> Option<Self> {
+        match raw.trim().to_ascii_lowercase().as_str() {
+            "inline" => Some(ImageDelivery::Inline),
+            "url" => Some(ImageDelivery::Url),
+            _ => None,
+        }
+    }
+}
+
+/// A configuration value that could not be used.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ConfigError {
+    /// Environment variable, `file: key`, or the file itself.
+    pub source: String,
+    pub message: String,
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.source, self.message)
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+#[derive(Clone, Debug)]
+pub struct AppConfig {
+    pub host: String,
+    pub port: u16,
+    pub workers: usize,
+    pub solution_ttl: Duration,
+    pub puzzle_ttl: Duration,
+    pub cache_prefill_per_size: usize,
+    pub cache_max_per_size: usize,
+    /// Puzzles every prefill size must reach before `/health/ready` first succeeds.
+    pub ready_min_prefill: usize,
+    /// Wrong answers allowed per puzzle before its id is discarded.
+    pub max_attempts: u32,
+    /// `/puzzle` requests allowed per client IP per minute; 0 disables the limit.
+    pub rate_limit_per_minute: u32,
+    pub generator_concurrency: usize,
+    pub cleanup_interval: Duration,
+    pub prefill_dimensions: Vec<(u32, u32)>,
+    pub log_level: String,
+    pub immediate_cache_cleanup: bool,
+    pub log_puzzle_seeds: bool,
+    pub image_delivery: ImageDelivery,
+    pub image_encoding: ImageEncoding,
+    /// Prefer WebP when the client's `Accept` header lists `image/webp`; WebP buckets are
+    /// then prefilled alongside `image_encoding`.
+    pub negotiate_image_encoding: bool,
+    pub image_quality: u8,
+    /// Background styles and their relative weights; one is drawn per puzzle.
+    pub background_styles: Vec<(BackgroundStyle, u32)>,
+    pub piece_style: PieceStyle,
+    /// Perturbation level for slider pieces; see [`Difficulty::perturbation`].
+    pub difficulty: Difficulty,
+    /// Sites selectable with `/puzzle?sitekey=...`, from `sites` or `PUZZLE_SITES_FILE`.
+    pub sites: SiteRegistry,
+    /// Reject `/puzzle` requests that do not name a configured site.
+    pub require_site_key: bool,
+    /// Bearer token for the `/admin` endpoints; they are disabled while unset.
+    pub admin_token: Option<String>,
+    /// Where outstanding solutions and cached puzzles are saved on shutdown and restored
+    /// from on startup.
+    pub snapshot_file: Option<PathBuf>,
+    /// Also save the puzzle cache (not solutions) to `snapshot_file` this often.
+    pub snapshot_interval: Option<Duration>,
+}
+
+impl AppConfig {
+    /// Defaults overridden by environment variables.
+    pub fn from_env() -> Result<Self, ConfigError> {
+        Self::load(None)
+    }
+
+    /// Defaults, overridden by `file` (YAML for `.yaml`/`.yml`, TOML otherwise), then by
+    /// environment variables.
+    pub fn load(file: Option<&Path>) -> Result<Self, ConfigError> {
+        let mut settings = Settings::default();
+        if let Some(path) = file {
+            settings.read_file(path)?;
+        }
+        settings.read_env()?;
+        settings.build()
+    }
+
+    /// The effective configuration in config-file TOML, with each secret replaced by a
+    /// distinct placeholder so the output loads again.
+    pub fn to_toml(&self) -> String {
+        layer::to_toml(self)
+    }
+}
+
+/// The built-in defaults alone; no file or environment variable is read.
+impl Default for AppConfig {
+    fn default() -> Self {
+        Settings::default()
+            .build()
+            .expect("built-in defaults are valid")
+    }
+}
+
+/// The live configuration. Readers take a snapshot with [`SharedConfig::load`]; a reload
+/// swaps the whole [`AppConfig`] at once, so a request never sees half of each.
+#[derive(Clone, Debug)]
+pub struct SharedConfig {
+    current: Arc<RwLock<Arc<AppConfig>>>,
+    /// Config file to re-read on reload; `None` re-reads the environment only.
+    source: Option<PathBuf>,
+    reloadable: bool,
+}
+
+impl SharedConfig {
+    /// A fixed configuration; [`SharedConfig::reload`] fails.
+    pub fn new(config: Arc<AppConfig>) -> Self {
+        Self {
+            current: Arc::new(RwLock::new(config)),
+            source: None,
+            reloadable: false,
+        }
+    }
+
+    /// Loads the configuration from `file` and the environment, remembering where it came
+    /// from for later reloads.
+    pub fn load_from(file: Option<PathBuf>) -> Result<Self, ConfigError> {
+        let config = AppConfig::load(file.as_deref())?;
+        Ok(Self {
+            current: Arc::new(RwLock::new(Arc::new(config))),
+            source: file,
+            reloadable: true,
+        })
+    }
+
+    pub fn load(&self) -> Arc<AppConfig> {
+        self.current.read().clone()
+    }
+
+    pub fn store(&self, config: Arc<AppConfig>) {
+        *self.current.write() = config;
+    }
+
+    /// Applies `change` to a copy of the current configuration and swaps it in, holding
+    /// the lock so concurrent updates do not overwrite each other.
+    pub fn update(&self, change: impl FnOnce(&mut AppConfig)) -> Arc<AppConfig> {
+        let mut current = self.current.write();
+        let mut next = AppConfig::clone(&current);
+        change(&mut next);
+        *current = Arc::new(next);
+        current.clone()
+    }
+
+    /// Re-reads the original sources and swaps the result in. On error the current
+    /// configuration stays in place.
+    pub fn reload(&self) -> Result<Arc<AppConfig>, ConfigError> {
+        if !self.reloadable {
+            return Err(ConfigError {
+                source: "reload".into(),
+                message: "this configuration was not loaded from a file or the environment".into(),
+            });
+        }
+        let config = Arc::new(AppConfig::load(self.source.as_deref())?);
+        self.store(config.clone());
+        Ok(config)
+    }
+}
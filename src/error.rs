@@ -0,0 +1,35 @@
+use std::fmt;
+
+/// Why a challenge could not be generated.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PuzzleError {
+    /// Width or height outside `MIN_DIMENSION..=MAX_DIMENSION`.
+    InvalidDimensions { width: u32, height: u32 },
+    /// Piece or icon count out of range, or the shapes do not fit on the image.
+    InvalidLayout(String),
+    /// The background source could not produce an image.
+    Background(String),
+    /// Encoding the rendered image failed.
+    Encode(String),
+    /// The generator queue is closed or the generation task died.
+    Unavailable,
+    /// The server is shutting down and no longer hands out challenges.
+    ShuttingDown,
+}
+
+impl fmt::Display for PuzzleError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PuzzleError::InvalidDimensions { width, height } => {
+                write!(f, "invalid puzzle dimensions {width}x{height}")
+            }
+            PuzzleError::InvalidLayout(reason) => write!(f, "invalid puzzle layout: {reason}"),
+            PuzzleError::Background(reason) => write!(f, "background source failed: {reason}"),
+            PuzzleError::Encode(reason) => write!(f, "failed to encode image: {reason}"),
+            PuzzleError::Unavailable => write!(f, "puzzle generator unavailable"),
+            PuzzleError::ShuttingDown => write!(f, "server is shutting down"),
+        }
+    }
+}
+
+impl std::error::Error for PuzzleError {}
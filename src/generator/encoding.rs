@@ -0,0 +1,171 @@
+use base64::Engine;
+use image::{codecs::png::CompressionType, DynamicImage, GenericImageView};
+
+use crate::error::PuzzleError;
+
+/// Container format for a single rendered image.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ImageFormat {
+    Png,
+    Jpeg,
+    WebP,
+}
+
+impl ImageFormat {
+    pub fn mime(self) -> &'static str {
+        match self {
+            ImageFormat::Png => "image/png",
+            ImageFormat::Jpeg => "image/jpeg",
+            ImageFormat::WebP => "image/webp",
+        }
+    }
+
+    pub fn extension(self) -> &'static str {
+        match self {
+            ImageFormat::Png => "png",
+            ImageFormat::Jpeg => "jpg",
+            ImageFormat::WebP => "webp",
+        }
+    }
+
+    /// `quality` (1-100) applies to the lossy formats and is ignored for PNG.
+    pub fn encode(self, image: &DynamicImage, quality: u8) -> Result<Vec<u8>, PuzzleError> {
+        match self {
+            ImageFormat::Png => encode_png(image),
+            ImageFormat::Jpeg => encode_jpeg(image, quality),
+            ImageFormat::WebP => encode_webp(image, quality),
+        }
+    }
+}
+
+/// Format pair used for one challenge: an opaque-friendly background and an alpha-capable piece.
+#[derive(
+    Clone, Copy, Debug, Default, PartialEq, Eq, Hash, serde::Serialize, serde::Deserialize,
+)]
+pub enum ImageEncoding {
+    #[default]
+    Png,
+    /// Lossy JPEG background, PNG piece.
+    Jpeg,
+    /// Lossy WebP for both background and piece, keeping alpha.
+    WebP,
+}
+
+impl ImageEncoding {
+    pub fn name(self) -> &'static str {
+        match self {
+            ImageEncoding::Png => "png",
+            ImageEncoding::Jpeg => "jpeg",
+            ImageEncoding::WebP => "webp",
+        }
+    }
+
+    pub fn parse(raw: &str) -> Option<Self> {
+        match raw.trim().to_ascii_lowercase().as_str() {
+            "png" => Some(ImageEncoding::Png),
+            "jpeg" | "jpg" => Some(ImageEncoding::Jpeg),
+            "webp" => Some(ImageEncoding::WebP),
+            _ => None,
+        }
+    }
+
+    /// Picks WebP for clients that advertise it in their `Accept` header. An entry with
+    /// `q=0` refuses the type and is skipped.
+    pub fn negotiate(accept: &str, fallback: Self) -> Self {
+        let accepts_webp = accept.split(',').any(|entry| {
+            let mut params = entry.split(';').map(str::trim);
+            let media_type = params.next().unwrap_or_default();
+            let quality = params
+                .filter_map(|param| param.strip_prefix("q="))
+                .find_map(|q| q.parse::<f32>().ok())
+                .unwrap_or(1.0);
+            media_type.eq_ignore_ascii_case("image/webp") && quality > 0.0
+        });
+        if accepts_webp {
+            ImageEncoding::WebP
+        } else {
+            fallback
+        }
+    }
+
+    pub fn background(self) -> ImageFormat {
+        match self {
+            ImageEncoding::Png => ImageFormat::Png,
+            ImageEncoding::Jpeg => ImageFormat::Jpeg,
+            ImageEncoding::WebP => ImageFormat::WebP,
+        }
+    }
+
+    pub fn piece(self) -> ImageFormat {
+        match self {
+            ImageEncoding::Png | ImageEncoding::Jpeg => ImageFormat::Png,
+            ImageEncoding::WebP => ImageFormat::WebP,
+        }
+    }
+}
+
+pub fn image_to_base64(image: image::DynamicImage) -> Result<String, PuzzleError> {
+    Ok(bytes_to_base64(&encode_png(&image)?))
+}
+
+/// Base64 for inline JSON delivery; cached entries only hold the encoded bytes.
+pub fn bytes_to_base64(bytes: &[u8]) -> String {
+    base64::engine::general_purpose::STANDARD.encode(bytes)
+}
+
+pub fn encode_png(image: &image::DynamicImage) -> Result<Vec<u8>, PuzzleError> {
+    encode_png_with(image, CompressionType::Default)
+}
+
+/// [`encode_png`] with an explicit zlib level, for trading CPU against payload size.
+pub fn encode_png_with(
+    image: &image::DynamicImage,
+    compression: CompressionType,
+) -> Result<Vec<u8>, PuzzleError> {
+    use image::codecs::png::{FilterType, PngEncoder};
+    use image::ColorType;
+
+    let mut buffer = Vec::new();
+
+    let encoder = PngEncoder::new_with_quality(&mut buffer, compression, FilterType::Sub);
+
+    let (width, height) = image.dimensions();
+    let color_type = match image {
+        image::DynamicImage::ImageRgb8(_) => ColorType::Rgb8,
+        image::DynamicImage::ImageRgba8(_) => ColorType::Rgba8,
+        _ => ColorType::Rgba8,
+    };
+
+    encoder
+        .encode(image.as_bytes(), width, height, color_type)
+        .map_err(|err| PuzzleError::Encode(err.to_string()))?;
+
+    Ok(buffer)
+}
+
+fn encode_jpeg(image: &DynamicImage, quality: u8) -> Result<Vec<u8>, PuzzleError> {
+    use image::codecs::jpeg::JpegEncoder;
+    use image::ColorType;
+
+    // Only backgrounds are JPEG-encoded, and their piece holes are already opaque.
+    let rgb = image.to_rgb8();
+    let (width, height) = rgb.dimensions();
+
+    let mut buffer = Vec::new();
+    JpegEncoder::new_with_quality(&mut buffer, quality.clamp(1, 100))
+        .encode(&rgb, width, height, ColorType::Rgb8)
+        .map_err(|err| PuzzleError::Encode(err.to_string()))?;
+
+    Ok(buffer)
+}
+
+fn encode_webp(image: &DynamicImage, quality: u8) -> Result<Vec<u8>, PuzzleError> {
+    let rgba = image.to_rgba8();
+    let (width, height) = rgba.dimensions();
+
+    let memory = webp::Encoder::from_rgba(&rgba, width, height)
+        .encode_simple(false, quality.clamp(1, 100) as f32)
+        .map_err(|err| PuzzleError::Encode(format!("{err:?}")))?;
+
+    Ok(memory.to_vec())
+}
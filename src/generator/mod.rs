@@ -1,24 +1,55 @@
-use std::{sync::Arc, time::Instant};
-
+use std::{
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc,
+    },
+    time::Instant,
+};
+
+use bytes::Bytes;
 use dashmap::DashMap;
-use tokio::{spawn, sync::{mpsc, Semaphore}, task::spawn_blocking};
-
-use crate::{cache::ExpiringCache, config::AppConfig, puzzle::SliderPuzzle};
-
+use parking_lot::RwLock;
+use rand::{rngs::StdRng, SeedableRng};
+use tokio::{
+    select, spawn,
+    sync::{mpsc, watch, Semaphore},
+    task::spawn_blocking,
+};
+
+use crate::{
+    background::WeightedBackgrounds, cache::ExpiringCache, click::ClickPuzzle, config::AppConfig,
+    error::PuzzleError, puzzle::SliderPuzzle, render::PieceStyle, site::SiteConfig,
+};
+
+mod encoding;
 mod model;
+mod snapshot;
 
-pub use model::{CachedSolution, PuzzleImages};
+pub use encoding::{
+    bytes_to_base64, encode_png, encode_png_with, image_to_base64, ImageEncoding, ImageFormat,
+};
+pub use model::{
+    Answer, CachedSolution, Challenge, ChallengeKind, PiecePosition, PuzzleImages, PuzzleKey,
+};
 
 #[derive(Clone)]
 pub struct PuzzleGenerator {
-    cache: ExpiringCache<(u32, u32), PuzzleImages>,
+    cache: ExpiringCache<PuzzleKey, PuzzleImages>,
     request_tx: mpsc::Sender<GenerateRequest>,
     solutions: DashMap<String, CachedSolution>,
+    /// Swapped whole by [`PuzzleGenerator::reconfigure`]; each generation reads a snapshot.
+    options: Arc<RwLock<Arc<RenderOptions>>>,
+    semaphore: Arc<Semaphore>,
+    concurrency: Arc<AtomicUsize>,
+    /// Flipped once by [`PuzzleGenerator::shutdown`].
+    shutdown: Arc<watch::Sender<bool>>,
+    /// Latched once every prefill size has reached the readiness minimum.
+    prefilled: Arc<AtomicBool>,
 }
 
 struct GenerateRequest {
-    dimensions: (u32, u32),
-    response: mpsc::Sender<Arc<PuzzleImages>>,
+    key: PuzzleKey,
+    response: mpsc::Sender<Result<Arc<PuzzleImages>, PuzzleError>>,
 }
 
 impl PuzzleGenerator {
@@ -29,110 +60,262 @@ impl PuzzleGenerator {
 
         let cache_dispatch = cache.clone();
         let semaphore_dispatch = semaphore.clone();
+        let options = Arc::new(RwLock::new(Arc::new(RenderOptions::from_config(&config))));
+        let options_dispatch = options.clone();
+        let (shutdown, _) = watch::channel(false);
+        let mut stop_dispatch = shutdown.subscribe();
 
         spawn(async move {
             let mut rx = rx;
-            while let Some(GenerateRequest { dimensions, response }) = rx.recv().await {
+            loop {
+                let GenerateRequest { key, response } = select! {
+                    request = rx.recv() => match request {
+                        Some(request) => request,
+                        None => break,
+                    },
+                    _ = stopped(&mut stop_dispatch) => break,
+                };
                 let cache = cache_dispatch.clone();
                 let semaphore = semaphore_dispatch.clone();
+                let options = options_dispatch.read().clone();
+                let mut stop = stop_dispatch.clone();
 
                 spawn(async move {
                     let start = Instant::now();
-                    let permit = match semaphore.acquire_owned().await {
-                        Ok(permit) => permit,
-                        Err(err) => {
-                            tracing::error!(error=?err, "Failed to acquire semaphore");
+                    // Requests still waiting for a permit are cancelled by a shutdown.
+                    let permit = select! {
+                        permit = semaphore.acquire_owned() => match permit {
+                            Ok(permit) => permit,
+                            Err(err) => {
+                                tracing::error!(error=?err, "Failed to acquire semaphore");
+                                return;
+                            }
+                        },
+                        _ = stopped(&mut stop) => {
+                            let _ = response.send(Err(PuzzleError::ShuttingDown)).await;
                             return;
                         }
                     };
 
-                    let generation = spawn_blocking(move || generate_puzzle(dimensions));
+                    let generation =
+                        spawn_blocking(move || generate_puzzle(key, rand::random(), &options));
 
-                    match generation.await {
+                    let result = match generation.await {
                         Ok(Ok(images)) => {
-                            cache.insert(dimensions, images.clone());
-                            let arc = Arc::new(images);
-                            if let Err(err) = response.send(arc).await {
-                                tracing::warn!(dimensions=?dimensions, error=?err, "Failed to deliver generated puzzle");
-                            }
+                            cache.insert(key, images.clone());
 
                             tracing::info!(
-                                dimensions=?dimensions,
+                                key=?key,
                                 elapsed_ms = start.elapsed().as_millis(),
                                 "Background puzzle generated"
                             );
+                            Ok(Arc::new(images))
                         }
                         Ok(Err(err)) => {
-                            tracing::error!(dimensions=?dimensions, error=?err, "Failed to generate slider puzzle");
+                            tracing::error!(key=?key, error=%err, "Failed to generate puzzle");
+                            Err(err)
                         }
                         Err(err) => {
-                            tracing::error!(dimensions=?dimensions, error=?err, "Generation task join error");
+                            tracing::error!(key=?key, error=?err, "Generation task join error");
+                            Err(PuzzleError::Unavailable)
                         }
-                    }
+                    };
+
+                    // Prefill requests drop their receiver, so a closed channel is expected.
+                    let _ = response.send(result).await;
 
                     drop(permit);
                 });
             }
+
+            // Cancel whatever was still queued when the shutdown began.
+            rx.close();
+            let mut cancelled = 0;
+            while let Ok(GenerateRequest { response, .. }) = rx.try_recv() {
+                let _ = response.try_send(Err(PuzzleError::ShuttingDown));
+                cancelled += 1;
+            }
+            if cancelled > 0 {
+                tracing::info!(cancelled, "Cancelled queued generation requests");
+            }
         });
 
         Self {
             cache,
             request_tx: tx,
             solutions: DashMap::new(),
+            options,
+            semaphore,
+            concurrency: Arc::new(AtomicUsize::new(config.generator_concurrency)),
+            shutdown: Arc::new(shutdown),
+            prefilled: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    /// Stops handing out challenges and cancels queued generation requests. Generations
+    /// already running finish in the background; solutions stay verifiable.
+    pub fn shutdown(&self) {
+        self.shutdown.send_replace(true);
+    }
+
+    pub fn is_shutting_down(&self) -> bool {
+        *self.shutdown.borrow()
+    }
+
+    /// Applies a reloaded configuration: cache TTL and limits, render options and
+    /// generation concurrency. Cached puzzles and solutions are kept; the request queue's
+    /// capacity is fixed at startup.
+    pub fn reconfigure(&self, config: &AppConfig) {
+        self.cache
+            .set_limits(config.puzzle_ttl, config.cache_max_per_size);
+        *self.options.write() = Arc::new(RenderOptions::from_config(config));
+
+        let target = config.generator_concurrency;
+        let previous = self.concurrency.swap(target, Ordering::SeqCst);
+        if target > previous {
+            self.semaphore.add_permits(target - previous);
+        } else if target < previous {
+            // Retire permits as in-flight generations release them.
+            let semaphore = self.semaphore.clone();
+            let surplus = (previous - target) as u32;
+            spawn(async move {
+                if let Ok(permits) = semaphore.acquire_many_owned(surplus).await {
+                    permits.forget();
+                }
+            });
         }
     }
 
-    pub async fn get_puzzle(&self, width: u32, height: u32) -> Option<Arc<PuzzleImages>> {
-        if let Some(images) = self.cache.pop(&(width, height)) {
-            return Some(images);
+    pub async fn get_puzzle(
+        &self,
+        width: u32,
+        height: u32,
+    ) -> Result<Arc<PuzzleImages>, PuzzleError> {
+        self.get_challenge(PuzzleKey::slider(width, height)).await
+    }
+
+    pub async fn get_challenge(&self, key: PuzzleKey) -> Result<Arc<PuzzleImages>, PuzzleError> {
+        key.validate()?;
+        if self.is_shutting_down() {
+            return Err(PuzzleError::ShuttingDown);
+        }
+
+        if let Some(images) = self.cache.pop(&key) {
+            return Ok(images);
         }
 
         let (tx, mut rx) = mpsc::channel(1);
 
-        let request = GenerateRequest {
-            dimensions: (width, height),
-            response: tx,
-        };
+        let request = GenerateRequest { key, response: tx };
 
         if let Err(err) = self.request_tx.send(request).await {
-            tracing::error!(%width, %height, error=?err, "Failed to enqueue generation request");
-            return None;
+            tracing::error!(key=?key, error=?err, "Failed to enqueue generation request");
+            return Err(PuzzleError::Unavailable);
         }
 
-        rx.recv().await
+        rx.recv().await.unwrap_or(Err(PuzzleError::Unavailable))
     }
 
     pub fn fill_cache(&self, config: &AppConfig) {
-        for &(width, height) in &config.prefill_dimensions {
-            let current = self.cache.len_for(&(width, height));
-            if current >= config.cache_prefill_per_size {
-                continue;
+        if self.is_shutting_down() {
+            return;
+        }
+        for key in prefill_keys(config) {
+            if let Err(err) = self.refill(key, config.cache_prefill_per_size) {
+                tracing::warn!(key=?key, error=%err, "Failed to schedule prefill");
             }
+        }
+    }
 
-            let needed = config.cache_prefill_per_size - current;
-            for _ in 0..needed {
-                let (tx, _) = mpsc::channel(1);
-
-                let request = GenerateRequest {
-                    dimensions: (width, height),
-                    response: tx,
-                };
-
-                if let Err(err) = self.request_tx.try_send(request) {
-                    tracing::warn!(%width, %height, error=?err, "Failed to schedule prefill request");
-                    break;
+    /// Queues generations until `key`'s bucket would hold `target` puzzles and returns how
+    /// many were queued, which is fewer than needed if the queue fills up.
+    pub fn refill(&self, key: PuzzleKey, target: usize) -> Result<usize, PuzzleError> {
+        key.validate()?;
+        if self.is_shutting_down() {
+            return Err(PuzzleError::ShuttingDown);
+        }
+        let needed = target.saturating_sub(self.cache.len_for(&key));
+        for queued in 0..needed {
+            let (tx, _) = mpsc::channel(1);
+            let request = GenerateRequest { key, response: tx };
+            match self.request_tx.try_send(request) {
+                Ok(()) => {}
+                Err(mpsc::error::TrySendError::Full(_)) => {
+                    tracing::warn!(key=?key, queued, needed, "Generation queue full, refill cut short");
+                    return Ok(queued);
                 }
+                Err(mpsc::error::TrySendError::Closed(_)) => return Err(PuzzleError::Unavailable),
             }
         }
+        Ok(needed)
+    }
+
+    /// Drops every cached puzzle for `key`; returns how many were removed.
+    pub fn flush(&self, key: &PuzzleKey) -> usize {
+        self.cache.remove_bucket(key)
+    }
+
+    /// Cached puzzles per bucket, including buckets filled on demand.
+    pub fn cache_levels(&self) -> Vec<(PuzzleKey, usize)> {
+        self.cache.bucket_lens()
+    }
+
+    /// Whether every prefill size has held at least `ready_min_prefill` puzzles at some
+    /// point. Once reached it stays true, so serving traffic does not flip readiness.
+    pub fn prefill_reached(&self, config: &AppConfig) -> bool {
+        if self.prefilled.load(Ordering::Relaxed) {
+            return true;
+        }
+        let reached =
+            prefill_keys(config).all(|key| self.cache.len_for(&key) >= config.ready_min_prefill);
+        if reached {
+            self.prefilled.store(true, Ordering::Relaxed);
+        }
+        reached
+    }
+
+    /// Cached puzzles per prefill size, as `(key, cached)`.
+    pub fn prefill_levels(&self, config: &AppConfig) -> Vec<(PuzzleKey, usize)> {
+        prefill_keys(config)
+            .map(|key| (key, self.cache.len_for(&key)))
+            .collect()
+    }
+
+    /// False once the dispatcher has stopped, after a shutdown or because its task died.
+    pub fn dispatcher_running(&self) -> bool {
+        !self.request_tx.is_closed()
     }
 
-    pub fn cache_solution(&self, id: String, solution: f64, expires_at: u64) {
+    /// Generation requests waiting for the dispatcher.
+    pub fn queue_depth(&self) -> usize {
+        self.request_tx.max_capacity() - self.request_tx.capacity()
+    }
+
+    pub fn queue_capacity(&self) -> usize {
+        self.request_tx.max_capacity()
+    }
+
+    pub fn solution_count(&self) -> usize {
+        self.solutions.len()
+    }
+
+    pub fn cache_solution(
+        &self,
+        id: String,
+        answer: Answer,
+        expires_at: u64,
+        images: Option<Arc<PuzzleImages>>,
+        site: Option<Arc<SiteConfig>>,
+    ) {
         self.solutions.insert(
             id,
             CachedSolution {
-                solution,
+                answer,
+                images,
                 expires_at,
                 attempts: 0,
+                site,
+                verified: false,
             },
         );
     }
@@ -150,6 +333,17 @@ impl PuzzleGenerator {
         }
     }
 
+    /// Flags a site-scoped solution as solved, keeping it for `/siteverify`.
+    pub fn mark_verified(&self, id: &str) -> bool {
+        match self.solutions.get_mut(id) {
+            Some(mut entry) => {
+                entry.verified = true;
+                true
+            }
+            None => false,
+        }
+    }
+
     pub fn remove_solution(&self, id: &str) -> Option<CachedSolution> {
         self.solutions.remove(id).map(|(_, value)| value)
     }
@@ -159,7 +353,7 @@ impl PuzzleGenerator {
         self.remove_solution(id)
     }
 
-    pub fn cache_len(&self, key: &(u32, u32)) -> usize {
+    pub fn cache_len(&self, key: &PuzzleKey) -> usize {
         self.cache.len_for(key)
     }
 
@@ -170,18 +364,136 @@ impl PuzzleGenerator {
     pub fn cleanup(&self) -> (usize, usize) {
         self.cache.clean_expired()
     }
+
+    /// Drops solutions (and any images held for them) that expired before `now`.
+    pub fn cleanup_solutions(&self, now: u64) -> usize {
+        let before = self.solutions.len();
+        self.solutions.retain(|_, entry| entry.expires_at > now);
+        before.saturating_sub(self.solutions.len())
+    }
+}
+
+/// The cache buckets `fill_cache` keeps topped up. With negotiation on, WebP buckets are
+/// warmed too so negotiated requests are served from the cache.
+fn prefill_keys(config: &AppConfig) -> impl Iterator<Item = PuzzleKey> + '_ {
+    let negotiated = (config.negotiate_image_encoding
+        && config.image_encoding != ImageEncoding::WebP)
+        .then_some(ImageEncoding::WebP);
+    let encodings = std::iter::once(config.image_encoding).chain(negotiated);
+    encodings.flat_map(move |encoding| {
+        config
+            .prefill_dimensions
+            .iter()
+            .map(move |&(width, height)| {
+                PuzzleKey::slider(width, height)
+                    .with_encoding(encoding)
+                    .with_difficulty(config.difficulty)
+            })
+    })
+}
+
+/// Resolves once the generator is shutting down, or its last handle is gone.
+async fn stopped(shutdown: &mut watch::Receiver<bool>) {
+    let _ = shutdown.wait_for(|stopping| *stopping).await;
+}
+
+/// Everything besides the key and seed that shapes a rendered challenge.
+#[derive(Clone, Debug)]
+pub struct RenderOptions {
+    /// JPEG/WebP quality, see `PUZZLE_IMAGE_QUALITY`.
+    pub quality: u8,
+    pub backgrounds: WeightedBackgrounds,
+    pub piece_style: PieceStyle,
+}
+
+impl RenderOptions {
+    pub fn from_config(config: &AppConfig) -> Self {
+        Self {
+            quality: config.image_quality,
+            backgrounds: WeightedBackgrounds::from_styles(&config.background_styles),
+            piece_style: config.piece_style,
+        }
+    }
 }
 
-fn generate_puzzle(dimensions: (u32, u32)) -> Result<PuzzleImages, String> {
-    let (width, height) = dimensions;
-    let slider_puzzle = SliderPuzzle::from_dimensions(width, height)?;
+impl Default for RenderOptions {
+    fn default() -> Self {
+        Self {
+            quality: 80,
+            backgrounds: WeightedBackgrounds::default(),
+            piece_style: PieceStyle::default(),
+        }
+    }
+}
 
-    let puzzle_b64 = Arc::new(model::image_to_base64(slider_puzzle.cropped_puzzle.clone()));
-    let piece_b64 = Arc::new(model::image_to_base64(slider_puzzle.puzzle_piece.clone()));
+/// Renders one challenge from an explicit seed.
+///
+/// The server draws a fresh seed per puzzle and records it in [`PuzzleImages::seed`],
+/// so an operator can call this offline with the logged key and seed (plus options built
+/// from the same configuration) to get the exact images a user was shown.
+pub fn generate_puzzle(
+    key: PuzzleKey,
+    seed: u64,
+    options: &RenderOptions,
+) -> Result<PuzzleImages, PuzzleError> {
+    key.validate()?;
+    let PuzzleKey {
+        kind,
+        width,
+        height,
+        encoding,
+        difficulty,
+    } = key;
+    let quality = options.quality;
+    let background = |image| encode(image, encoding.background(), quality);
+    let piece = |image| encode(image, encoding.piece(), quality);
+    let mut rng = StdRng::seed_from_u64(seed);
+    let canvas = options.backgrounds.generate(width, height, &mut rng);
+
+    match kind {
+        ChallengeKind::Slider {
+            pieces,
+            orientation,
+        } => {
+            let slider_puzzle = SliderPuzzle::from_background_styled(
+                canvas,
+                pieces as usize,
+                orientation,
+                &options.piece_style,
+                &difficulty.perturbation(),
+                &mut rng,
+            )?;
+
+            Ok(PuzzleImages {
+                background: background(&slider_puzzle.cropped_puzzle)?,
+                pieces: slider_puzzle
+                    .pieces
+                    .iter()
+                    .map(|slider_piece| piece(&slider_piece.image))
+                    .collect::<Result<_, _>>()?,
+                encoding,
+                challenge: Challenge::from(&slider_puzzle),
+                seed,
+            })
+        }
+        ChallengeKind::Click { icons } => {
+            let click_puzzle = ClickPuzzle::from_background(canvas, icons as usize, &mut rng)?;
+
+            Ok(PuzzleImages {
+                background: background(&click_puzzle.image)?,
+                pieces: vec![piece(&click_puzzle.hint)?],
+                encoding,
+                challenge: Challenge::from(&click_puzzle),
+                seed,
+            })
+        }
+    }
+}
 
-    Ok(PuzzleImages {
-        puzzle_b64,
-        piece_b64,
-        slider: Arc::new(slider_puzzle),
-    })
+fn encode(
+    image: &image::DynamicImage,
+    format: ImageFormat,
+    quality: u8,
+) -> Result<Bytes, PuzzleError> {
+    format.encode(image, quality).map(Bytes::from)
 }
@@ -1,43 +1,232 @@
-use std::sync::Arc;
+use std::{fmt, sync::Arc};
 
-use image::GenericImageView;
-use base64::Engine;
+use bytes::Bytes;
 
-use crate::puzzle::SliderPuzzle;
+use super::encoding::ImageEncoding;
+use crate::{
+    click::{ClickPuzzle, Icon, MAX_ICONS, MIN_ICONS},
+    error::PuzzleError,
+    puzzle::{validate_dimensions, Orientation, SliderPuzzle, MAX_PIECES},
+    render::Difficulty,
+    site::SiteConfig,
+};
 
-#[derive(Clone)]
-pub struct PuzzleImages {
-    pub puzzle_b64: Arc<String>,
-    pub piece_b64: Arc<String>,
-    pub slider: Arc<SliderPuzzle>,
+/// Challenge family plus the parameters that change its cached output.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, serde::Serialize, serde::Deserialize)]
+pub enum ChallengeKind {
+    Slider {
+        pieces: u8,
+        orientation: Orientation,
+    },
+    Click {
+        icons: u8,
+    },
 }
 
-#[derive(Clone, Debug)]
-pub struct CachedSolution {
-    pub solution: f64,
-    pub expires_at: u64,
-    pub attempts: u32,  // 尝试次数
+/// Cache bucket key: one queue per challenge kind, size, output encoding and difficulty.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, serde::Serialize, serde::Deserialize)]
+pub struct PuzzleKey {
+    pub kind: ChallengeKind,
+    pub width: u32,
+    pub height: u32,
+    pub encoding: ImageEncoding,
+    pub difficulty: Difficulty,
+}
+
+impl PuzzleKey {
+    pub fn slider(width: u32, height: u32) -> Self {
+        Self::slider_variant(width, height, 1, Orientation::Horizontal)
+    }
+
+    pub fn slider_variant(width: u32, height: u32, pieces: u8, orientation: Orientation) -> Self {
+        Self {
+            kind: ChallengeKind::Slider {
+                pieces,
+                orientation,
+            },
+            width,
+            height,
+            encoding: ImageEncoding::Png,
+            difficulty: Difficulty::Off,
+        }
+    }
+
+    pub fn click(width: u32, height: u32, icons: u8) -> Self {
+        Self {
+            kind: ChallengeKind::Click { icons },
+            width,
+            height,
+            encoding: ImageEncoding::Png,
+            difficulty: Difficulty::Off,
+        }
+    }
+
+    pub fn with_encoding(self, encoding: ImageEncoding) -> Self {
+        Self { encoding, ..self }
+    }
+
+    pub fn with_difficulty(self, difficulty: Difficulty) -> Self {
+        Self { difficulty, ..self }
+    }
+
+    /// Rejects keys that could never be generated, before they reach the queue.
+    pub fn validate(&self) -> Result<(), PuzzleError> {
+        validate_dimensions(self.width, self.height)?;
+        match self.kind {
+            ChallengeKind::Slider { pieces, .. }
+                if !(1..=MAX_PIECES).contains(&(pieces as usize)) =>
+            {
+                Err(PuzzleError::InvalidLayout(format!(
+                    "pieces must be between 1 and {MAX_PIECES}"
+                )))
+            }
+            ChallengeKind::Click { icons }
+                if !(MIN_ICONS..=MAX_ICONS).contains(&(icons as usize)) =>
+            {
+                Err(PuzzleError::InvalidLayout(format!(
+                    "n must be between {MIN_ICONS} and {MAX_ICONS}"
+                )))
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Where a slider piece was cut from, relative to the image size.
+#[derive(Clone, Copy, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct PiecePosition {
+    pub x: f64,
+    pub y: f64,
+}
+
+/// Everything about a rendered challenge except its pixels.
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+pub enum Challenge {
+    Slider {
+        /// Pieces in slide order.
+        pieces: Vec<PiecePosition>,
+        orientation: Orientation,
+        /// Shadow border around each piece image; see [`SliderPuzzle::piece_padding`].
+        padding: u32,
+    },
+    Click {
+        icons: Vec<Icon>,
+        points: Vec<(f64, f64)>,
+        tolerance: (f64, f64),
+    },
+}
+
+impl Challenge {
+    pub fn answer(&self) -> Answer {
+        match self {
+            Challenge::Slider {
+                pieces,
+                orientation,
+                ..
+            } => Answer::Slider {
+                targets: pieces
+                    .iter()
+                    .map(|piece| match orientation {
+                        Orientation::Horizontal => piece.x,
+                        Orientation::Vertical => piece.y,
+                    })
+                    .collect(),
+                orientation: *orientation,
+            },
+            Challenge::Click {
+                points, tolerance, ..
+            } => Answer::Click {
+                points: points.clone(),
+                tolerance: *tolerance,
+            },
+        }
+    }
+}
+
+impl From<&SliderPuzzle> for Challenge {
+    fn from(slider: &SliderPuzzle) -> Self {
+        Challenge::Slider {
+            pieces: slider
+                .pieces
+                .iter()
+                .map(|piece| PiecePosition {
+                    x: piece.x,
+                    y: piece.y,
+                })
+                .collect(),
+            orientation: slider.orientation,
+            padding: slider.piece_padding,
+        }
+    }
 }
 
-pub fn image_to_base64(image: image::DynamicImage) -> String {
-    use image::codecs::png::{CompressionType, FilterType, PngEncoder};
-    use image::ColorType;
+impl From<&ClickPuzzle> for Challenge {
+    fn from(click: &ClickPuzzle) -> Self {
+        Challenge::Click {
+            icons: click.icons.clone(),
+            points: click.points.clone(),
+            tolerance: click.tolerance,
+        }
+    }
+}
 
-    let mut buffer = Vec::new();
+/// A cached challenge: encoded images plus the metadata needed to serve and verify it.
+///
+/// Only the encoded bytes are kept; base64 is produced per response when images are
+/// delivered inline. `Bytes` clones are reference-counted, so handing an entry to a
+/// solution or an HTTP body never copies the image.
+#[derive(Clone)]
+pub struct PuzzleImages {
+    pub background: Bytes,
+    /// Slider pieces in slide order, or the click-order hint strip for click challenges.
+    pub pieces: Vec<Bytes>,
+    pub encoding: ImageEncoding,
+    pub challenge: Challenge,
+    /// RNG seed the challenge was rendered from; see [`super::generate_puzzle`].
+    pub seed: u64,
+}
 
-    let encoder =
-        PngEncoder::new_with_quality(&mut buffer, CompressionType::Default, FilterType::Sub);
+impl PuzzleImages {
+    /// Encoded image bytes held by this entry, excluding struct and metadata overhead.
+    pub fn encoded_len(&self) -> usize {
+        self.background.len() + self.pieces.iter().map(Bytes::len).sum::<usize>()
+    }
+}
 
-    let (width, height) = image.dimensions();
-    let color_type = match &image {
-        image::DynamicImage::ImageRgb8(_) => ColorType::Rgb8,
-        image::DynamicImage::ImageRgba8(_) => ColorType::Rgba8,
-        _ => ColorType::Rgba8,
-    };
+// Summarised so logging a solution never dumps image bytes.
+impl fmt::Debug for PuzzleImages {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PuzzleImages")
+            .field("background_len", &self.background.len())
+            .field("pieces", &self.pieces.len())
+            .field("encoding", &self.encoding)
+            .field("challenge", &self.challenge)
+            .field("seed", &self.seed)
+            .finish()
+    }
+}
 
-    encoder
-        .encode(image.as_bytes(), width, height, color_type)
-        .expect("Failed to encode PNG");
+#[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
+pub enum Answer {
+    Slider {
+        targets: Vec<f64>,
+        orientation: Orientation,
+    },
+    Click {
+        points: Vec<(f64, f64)>,
+        tolerance: (f64, f64),
+    },
+}
 
-    base64::engine::general_purpose::STANDARD.encode(&buffer)
+#[derive(Clone, Debug)]
+pub struct CachedSolution {
+    pub answer: Answer,
+    /// Kept only when images are served by URL, so they expire with the solution.
+    pub images: Option<Arc<PuzzleImages>>,
+    pub expires_at: u64,
+    pub attempts: u32,  // 尝试次数
+    /// Site that issued the puzzle; only that site may verify it.
+    pub site: Option<Arc<SiteConfig>>,
+    /// Solved by the user and waiting for the site's backend to call `/siteverify`.
+    pub verified: bool,
 }
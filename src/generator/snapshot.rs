@@ -0,0 +1,266 @@
+//! Saving outstanding solutions and cached puzzles across a restart.
+//!
+//! The snapshot is a JSON file with image bytes in base64. It holds every answer a user
+//! could still submit, so it is written with owner-only permissions and removed once it
+//! has been restored. Periodic snapshots carry only the puzzle cache: a solution saved
+//! that way could be restored after it had already been redeemed.
+
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::Duration,
+};
+
+use base64::Engine;
+use bytes::Bytes;
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+
+use super::{
+    bytes_to_base64, Answer, CachedSolution, Challenge, ImageEncoding, PuzzleGenerator,
+    PuzzleImages, PuzzleKey,
+};
+use crate::site::SiteRegistry;
+
+const VERSION: u32 = 1;
+
+#[derive(Serialize, Deserialize)]
+struct Snapshot {
+    version: u32,
+    solutions: Vec<SolutionRecord>,
+    #[serde(default)]
+    puzzles: Vec<PuzzleRecord>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct PuzzleRecord {
+    key: PuzzleKey,
+    /// Unix seconds when the puzzle was generated.
+    created_at: u64,
+    images: ImagesRecord,
+}
+
+#[derive(Serialize, Deserialize)]
+struct SolutionRecord {
+    id: String,
+    answer: Answer,
+    expires_at: u64,
+    attempts: u32,
+    /// Site key; the site's settings are looked up again on restore.
+    site: Option<String>,
+    verified: bool,
+    images: Option<ImagesRecord>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct ImagesRecord {
+    background: String,
+    pieces: Vec<String>,
+    encoding: ImageEncoding,
+    challenge: Challenge,
+    seed: u64,
+}
+
+impl From<&PuzzleImages> for ImagesRecord {
+    fn from(images: &PuzzleImages) -> Self {
+        Self {
+            background: bytes_to_base64(&images.background),
+            pieces: images
+                .pieces
+                .iter()
+                .map(|piece| bytes_to_base64(piece))
+                .collect(),
+            encoding: images.encoding,
+            challenge: images.challenge.clone(),
+            seed: images.seed,
+        }
+    }
+}
+
+impl TryFrom<ImagesRecord> for PuzzleImages {
+    type Error = io::Error;
+
+    fn try_from(record: ImagesRecord) -> io::Result<Self> {
+        let decode = |data: &str| {
+            base64::engine::general_purpose::STANDARD
+                .decode(data)
+                .map(Bytes::from)
+                .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+        };
+        Ok(Self {
+            background: decode(&record.background)?,
+            pieces: record
+                .pieces
+                .iter()
+                .map(|piece| decode(piece))
+                .collect::<io::Result<_>>()?,
+            encoding: record.encoding,
+            challenge: record.challenge,
+            seed: record.seed,
+        })
+    }
+}
+
+impl PuzzleGenerator {
+    /// Writes the solutions that are still valid at `now` (unix seconds) and the cached
+    /// puzzles to `path`, replacing it atomically. Returns how many of each were saved.
+    pub fn save_snapshot(&self, path: &Path, now: u64) -> io::Result<(usize, usize)> {
+        self.write_snapshot(path, now, true)
+    }
+
+    /// Like [`PuzzleGenerator::save_snapshot`] but without solutions, for periodic
+    /// snapshots taken while puzzles are still being solved.
+    pub fn save_cache_snapshot(&self, path: &Path, now: u64) -> io::Result<usize> {
+        self.write_snapshot(path, now, false)
+            .map(|(_, puzzles)| puzzles)
+    }
+
+    fn write_snapshot(
+        &self,
+        path: &Path,
+        now: u64,
+        with_solutions: bool,
+    ) -> io::Result<(usize, usize)> {
+        let solutions: Vec<SolutionRecord> = self
+            .solutions
+            .iter()
+            .filter(|entry| with_solutions && entry.expires_at > now)
+            .map(|entry| SolutionRecord {
+                id: entry.key().clone(),
+                answer: entry.answer.clone(),
+                expires_at: entry.expires_at,
+                attempts: entry.attempts,
+                site: entry.site.as_ref().map(|site| site.site_key.clone()),
+                verified: entry.verified,
+                images: entry.images.as_deref().map(ImagesRecord::from),
+            })
+            .collect();
+        let puzzles: Vec<PuzzleRecord> = self
+            .cache
+            .entries()
+            .into_iter()
+            .map(|(key, images, age)| PuzzleRecord {
+                key,
+                created_at: now.saturating_sub(age.as_secs()),
+                images: ImagesRecord::from(images.as_ref()),
+            })
+            .collect();
+        let saved = (solutions.len(), puzzles.len());
+        let snapshot = Snapshot {
+            version: VERSION,
+            solutions,
+            puzzles,
+        };
+        let json = serde_json::to_vec(&snapshot)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+
+        let partial = partial_path(path);
+        write_private(&partial, &json)?;
+        fs::rename(&partial, path)?;
+        Ok(saved)
+    }
+
+    /// Loads solutions and cached puzzles saved by [`PuzzleGenerator::save_snapshot`],
+    /// skipping solutions that expired before `now` or belong to a site that is no longer
+    /// configured and puzzles older than the cache TTL, then deletes the file so the same
+    /// answers cannot be restored twice. Records whose images fail to decode are skipped
+    /// with a warning. A missing file restores nothing. Returns how many solutions and
+    /// puzzles were restored.
+    pub fn restore_snapshot(
+        &self,
+        path: &Path,
+        sites: &SiteRegistry,
+        now: u64,
+    ) -> io::Result<(usize, usize)> {
+        let json = match fs::read(path) {
+            Ok(json) => json,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok((0, 0)),
+            Err(err) => return Err(err),
+        };
+        let snapshot: Snapshot = serde_json::from_slice(&json)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+        if snapshot.version != VERSION {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("unsupported snapshot version {}", snapshot.version),
+            ));
+        }
+
+        let mut restored = 0;
+        for record in snapshot.solutions {
+            if record.expires_at <= now {
+                continue;
+            }
+            let site = match &record.site {
+                Some(site_key) => match sites.get(site_key) {
+                    Some(site) => Some(site.clone()),
+                    None => continue,
+                },
+                None => None,
+            };
+            let images = match record.images.map(PuzzleImages::try_from).transpose() {
+                Ok(images) => images.map(Arc::new),
+                Err(err) => {
+                    warn!(id = %record.id, error = %err, "Skipping undecodable snapshot solution");
+                    continue;
+                }
+            };
+            self.solutions.insert(
+                record.id,
+                CachedSolution {
+                    answer: record.answer,
+                    images,
+                    expires_at: record.expires_at,
+                    attempts: record.attempts,
+                    site,
+                    verified: record.verified,
+                },
+            );
+            restored += 1;
+        }
+
+        let mut warmed = 0;
+        for record in snapshot.puzzles {
+            let age = Duration::from_secs(now.saturating_sub(record.created_at));
+            let images = match PuzzleImages::try_from(record.images) {
+                Ok(images) => Arc::new(images),
+                Err(err) => {
+                    warn!(key = ?record.key, error = %err, "Skipping undecodable snapshot puzzle");
+                    continue;
+                }
+            };
+            if self.cache.insert_aged(record.key, images, age) {
+                warmed += 1;
+            }
+        }
+
+        fs::remove_file(path)?;
+        Ok((restored, warmed))
+    }
+}
+
+fn partial_path(path: &Path) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(".partial");
+    PathBuf::from(name)
+}
+
+#[cfg(unix)]
+fn write_private(path: &Path, contents: &[u8]) -> io::Result<()> {
+    use std::{io::Write, os::unix::fs::OpenOptionsExt};
+
+    let mut file = fs::OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .mode(0o600)
+        .open(path)?;
+    file.write_all(contents)?;
+    file.sync_all()
+}
+
+#[cfg(not(unix))]
+fn write_private(path: &Path, contents: &[u8]) -> io::Result<()> {
+    fs::write(path, contents)
+}
@@ -1,7 +1,20 @@
+mod click;
+mod error;
 mod puzzle;
 
-pub use puzzle::{verify_puzzle, SliderPuzzle};
+pub use click::{verify_clicks, ClickPuzzle, Icon};
+pub use error::PuzzleError;
+pub use puzzle::{
+    validate_dimensions, verify_pieces, verify_puzzle, Orientation, PuzzlePiece, SliderPuzzle,
+    MAX_DIMENSION, MIN_DIMENSION,
+};
+pub use slider_captcha_api as api;
+pub mod background;
 pub mod cache;
 pub mod config;
 pub mod generator;
+pub mod rate_limit;
+pub mod render;
 pub mod server;
+pub mod site;
+pub mod solver;
@@ -1,126 +1,243 @@
-use image::{DynamicImage, GenericImage, GenericImageView, Rgba};
-use rand::Rng;
+use image::{imageops, DynamicImage, RgbaImage};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+use crate::{
+    background::{BackgroundGenerator, Gradient},
+    error::PuzzleError,
+    render::{perturb_piece, shade_hole, style_piece, Perturbation, PieceStyle},
+};
+
+pub use crate::api::Orientation;
+
+pub const MAX_PIECES: usize = 3;
+
+/// Smallest width or height every challenge type can be laid out on.
+pub const MIN_DIMENSION: u32 = 20;
+/// Largest width or height accepted, bounding memory use per puzzle.
+pub const MAX_DIMENSION: u32 = 4096;
+
+pub fn validate_dimensions(width: u32, height: u32) -> Result<(), PuzzleError> {
+    let range = MIN_DIMENSION..=MAX_DIMENSION;
+    if range.contains(&width) && range.contains(&height) {
+        Ok(())
+    } else {
+        Err(PuzzleError::InvalidDimensions { width, height })
+    }
+}
 
 #[derive(Debug, Clone)]
 pub struct SliderPuzzle {
     pub cropped_puzzle: image::DynamicImage,
-    pub puzzle_piece: image::DynamicImage,
+    /// Pieces in the order the user slides them; a classic puzzle has one.
+    pub pieces: Vec<PuzzlePiece>,
+    pub orientation: Orientation,
+    /// Transparent border around every piece image, reserved for its drop shadow.
+    /// The piece's `x`/`y` refer to the cut-out itself, inside this border.
+    pub piece_padding: u32,
+}
+
+#[derive(Debug, Clone)]
+pub struct PuzzlePiece {
+    pub image: image::DynamicImage,
     pub x: f64,
     pub y: f64,
 }
 
 impl SliderPuzzle {
-    fn generate_random_image(width: u32, height: u32) -> DynamicImage {
-        let mut rng = rand::thread_rng();
-        let mut image = DynamicImage::new_rgba8(width, height);
-
-        let r1 = rng.gen_range(100..=255);
-        let g1 = rng.gen_range(100..=255);
-        let b1 = rng.gen_range(100..=255);
-
-        let r2 = rng.gen_range(50..=200);
-        let g2 = rng.gen_range(50..=200);
-        let b2 = rng.gen_range(50..=200);
-
-        let gradient_type = rng.gen_range(0..3);
-
-        for y in 0..height {
-            for x in 0..width {
-                let ratio = match gradient_type {
-                    0 => x as f32 / width as f32,
-                    1 => y as f32 / height as f32,
-                    _ => ((x as f32 / width as f32) + (y as f32 / height as f32)) / 2.0,
-                };
+    pub fn from_dimensions(width: u32, height: u32) -> Result<SliderPuzzle, PuzzleError> {
+        Self::with_pieces(width, height, 1)
+    }
 
-                let r = (r1 as f32 * (1.0 - ratio) + r2 as f32 * ratio) as u8;
-                let g = (g1 as f32 * (1.0 - ratio) + g2 as f32 * ratio) as u8;
-                let b = (b1 as f32 * (1.0 - ratio) + b2 as f32 * ratio) as u8;
+    pub fn from_dimensions_with_rng<R: Rng + ?Sized>(
+        width: u32,
+        height: u32,
+        rng: &mut R,
+    ) -> Result<SliderPuzzle, PuzzleError> {
+        Self::with_layout_and_rng(width, height, 1, Orientation::Horizontal, rng)
+    }
 
-                image.put_pixel(x, y, Rgba([r, g, b, 255]));
-            }
-        }
+    /// Same seed and dimensions always produce the same puzzle, pixel for pixel.
+    pub fn from_seed(width: u32, height: u32, seed: u64) -> Result<SliderPuzzle, PuzzleError> {
+        Self::from_dimensions_with_rng(width, height, &mut StdRng::seed_from_u64(seed))
+    }
 
-        let num_shapes = rng.gen_range(2..=4);
-        for _ in 0..num_shapes {
-            let shape_r = rng.gen_range(0..=255);
-            let shape_g = rng.gen_range(0..=255);
-            let shape_b = rng.gen_range(0..=255);
-            let alpha = rng.gen_range(100..=200);
-
-            let cx = rng.gen_range(0..width);
-            let cy = rng.gen_range(0..height);
-            let radius = rng.gen_range(20..60);
-
-            for y in 0..height {
-                for x in 0..width {
-                    let dx = (x as i32 - cx as i32).abs();
-                    let dy = (y as i32 - cy as i32).abs();
-                    let dist_sq = (dx * dx + dy * dy) as f32;
-                    let radius_sq = (radius * radius) as f32;
-
-                    if dist_sq <= radius_sq {
-                        let old_pixel = image.get_pixel(x, y);
-                        let blend = alpha as f32 / 255.0;
-                        let r = ((shape_r as f32 * blend) + (old_pixel[0] as f32 * (1.0 - blend)))
-                            as u8;
-                        let g = ((shape_g as f32 * blend) + (old_pixel[1] as f32 * (1.0 - blend)))
-                            as u8;
-                        let b = ((shape_b as f32 * blend) + (old_pixel[2] as f32 * (1.0 - blend)))
-                            as u8;
-
-                        image.put_pixel(x, y, Rgba([r, g, b, 255]));
-                    }
-                }
-            }
-        }
+    pub fn with_pieces(width: u32, height: u32, count: usize) -> Result<SliderPuzzle, PuzzleError> {
+        Self::with_layout(width, height, count, Orientation::Horizontal)
+    }
 
-        image
+    pub fn with_layout(
+        width: u32,
+        height: u32,
+        count: usize,
+        orientation: Orientation,
+    ) -> Result<SliderPuzzle, PuzzleError> {
+        Self::with_layout_and_rng(width, height, count, orientation, &mut rand::thread_rng())
     }
 
-    pub fn from_dimensions(width: u32, height: u32) -> Result<SliderPuzzle, String> {
-        let input_image = Self::generate_random_image(width, height);
+    pub fn with_layout_and_rng<R: Rng + ?Sized>(
+        width: u32,
+        height: u32,
+        count: usize,
+        orientation: Orientation,
+        rng: &mut R,
+    ) -> Result<SliderPuzzle, PuzzleError> {
+        validate_dimensions(width, height)?;
+        validate_piece_count(count)?;
+
+        let background = Gradient.generate(width, height, &mut &mut *rng);
+        Self::from_background(background, count, orientation, rng)
+    }
 
-        let piece_width = width / 5;
-        let piece_height = height / 5;
+    /// Cuts `count` pieces out of an already rendered background.
+    pub fn from_background<R: Rng + ?Sized>(
+        input_image: RgbaImage,
+        count: usize,
+        orientation: Orientation,
+        rng: &mut R,
+    ) -> Result<SliderPuzzle, PuzzleError> {
+        Self::from_background_styled(
+            input_image,
+            count,
+            orientation,
+            &PieceStyle::default(),
+            &Perturbation::default(),
+            rng,
+        )
+    }
 
-        let mut rng = rand::thread_rng();
-        let start_x = rng.gen_range(0..(width - piece_width));
-        let start_y = rng.gen_range(piece_height..(2 * piece_height));
+    /// [`SliderPuzzle::from_background`] with explicit piece and hole rendering.
+    ///
+    /// An active `perturbation` re-samples each piece's texture (see
+    /// [`Difficulty`](crate::render::Difficulty));
+    /// the reported positions still point at the hole.
+    pub fn from_background_styled<R: Rng + ?Sized>(
+        input_image: RgbaImage,
+        count: usize,
+        orientation: Orientation,
+        style: &PieceStyle,
+        perturbation: &Perturbation,
+        rng: &mut R,
+    ) -> Result<SliderPuzzle, PuzzleError> {
+        let (width, height) = input_image.dimensions();
+        validate_dimensions(width, height)?;
+        validate_piece_count(count)?;
 
-        let mut puzzle_piece = DynamicImage::new_rgb8(piece_width, piece_height);
-        for y in 0..piece_height {
-            for x in 0..piece_width {
-                let pixel = input_image.get_pixel(start_x + x, start_y + y);
-                let rgba_pixel = Rgba([pixel[0], pixel[1], pixel[2], pixel[3]]);
-                puzzle_piece.put_pixel(x, y, rgba_pixel);
+        let piece_width = width / 5;
+        let piece_height = height / 5;
+        let perturbation = &match orientation {
+            Orientation::Horizontal => perturbation.within_margin(width, piece_width),
+            Orientation::Vertical => perturbation.within_margin(height, piece_height),
+        };
+
+        let mut origins: Vec<(u32, u32)> = Vec::with_capacity(count);
+        if count == 1 {
+            // The fixed coordinate stays in a narrow band; the moving one spans the image.
+            origins.push(match orientation {
+                Orientation::Horizontal => (
+                    rng.gen_range(0..(width - piece_width)),
+                    rng.gen_range(piece_height..(2 * piece_height)),
+                ),
+                Orientation::Vertical => (
+                    rng.gen_range(piece_width..(2 * piece_width)),
+                    rng.gen_range(0..(height - piece_height)),
+                ),
+            });
+        } else {
+            // Extra pieces may sit anywhere, as long as no two holes overlap.
+            for _ in 0..count {
+                let origin = (0..200)
+                    .map(|_| {
+                        (
+                            rng.gen_range(0..(width - piece_width)),
+                            rng.gen_range(0..(height - piece_height)),
+                        )
+                    })
+                    .find(|&(x, y)| {
+                        origins.iter().all(|&(ox, oy)| {
+                            x.abs_diff(ox) >= piece_width || y.abs_diff(oy) >= piece_height
+                        })
+                    })
+                    .ok_or_else(|| {
+                        PuzzleError::InvalidLayout(format!(
+                            "cannot place {count} pieces on a {width}x{height} image"
+                        ))
+                    })?;
+                origins.push(origin);
             }
         }
 
-        let mut cropped_image = DynamicImage::new_rgba8(width, height);
-        for y in 0..height {
-            for x in 0..width {
-                let pixel = input_image.get_pixel(x, y);
-                let mut rgba_pixel = Rgba([pixel[0], pixel[1], pixel[2], pixel[3]]);
-                if x >= start_x
-                    && x < start_x + piece_width
-                    && y >= start_y
-                    && y < start_y + piece_height
-                {
-                    rgba_pixel[3] = 0;
+        let pieces = origins
+            .iter()
+            .map(|&(start_x, start_y)| {
+                let cut_out = if perturbation.is_active() {
+                    perturb_piece(
+                        &input_image,
+                        (start_x, start_y),
+                        (piece_width, piece_height),
+                        perturbation,
+                        rng,
+                    )
+                } else {
+                    imageops::crop_imm(&input_image, start_x, start_y, piece_width, piece_height)
+                        .to_image()
+                };
+                PuzzlePiece {
+                    image: DynamicImage::ImageRgba8(style_piece(&cut_out, style)),
+                    y: (start_y as f64 / height as f64),
+                    x: (start_x as f64 / width as f64),
                 }
-                cropped_image.put_pixel(x, y, rgba_pixel);
-            }
+            })
+            .collect::<Vec<_>>();
+
+        let mut cropped_image = input_image;
+        for &origin in &origins {
+            shade_hole(
+                &mut cropped_image,
+                origin,
+                (piece_width, piece_height),
+                style,
+            );
         }
 
         Ok(SliderPuzzle {
-            cropped_puzzle: cropped_image,
-            puzzle_piece,
-            y: (start_y as f64 / height as f64),
-            x: (start_x as f64 / width as f64),
+            cropped_puzzle: DynamicImage::ImageRgba8(cropped_image),
+            pieces,
+            orientation,
+            piece_padding: style.padding(),
         })
     }
+
+    /// Target position of every piece along the moving axis, in slide order.
+    pub fn targets(&self) -> Vec<f64> {
+        self.pieces
+            .iter()
+            .map(|piece| match self.orientation {
+                Orientation::Horizontal => piece.x,
+                Orientation::Vertical => piece.y,
+            })
+            .collect()
+    }
+}
+
+fn validate_piece_count(count: usize) -> Result<(), PuzzleError> {
+    if (1..=MAX_PIECES).contains(&count) {
+        Ok(())
+    } else {
+        Err(PuzzleError::InvalidLayout(format!(
+            "piece count must be between 1 and {MAX_PIECES}, got {count}"
+        )))
+    }
 }
 
 pub fn verify_puzzle(solution: f64, submission: f64, error_margin: f64) -> bool {
     (solution - submission).abs() < error_margin
 }
+
+/// Multi-piece variant of [`verify_puzzle`]: every piece must be within the margin.
+pub fn verify_pieces(solution: &[f64], submission: &[f64], error_margin: f64) -> bool {
+    solution.len() == submission.len()
+        && solution
+            .iter()
+            .zip(submission)
+            .all(|(expected, actual)| verify_puzzle(*expected, *actual, error_margin))
+}
@@ -0,0 +1,71 @@
+//! Per-client request limits for `/puzzle`.
+//!
+//! Each client IP gets a fixed one-minute window. The limit itself lives in the
+//! configuration, so the admin API can change it without touching existing windows.
+//!
+//! Clients are keyed on the connection's peer address; `X-Forwarded-For` is not
+//! consulted, so behind a reverse proxy every client shares the proxy's window.
+
+use std::{
+    net::IpAddr,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use dashmap::DashMap;
+
+const WINDOW: Duration = Duration::from_secs(60);
+
+#[derive(Clone, Copy, Debug)]
+struct Window {
+    started: Instant,
+    requests: u32,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct RateLimiter {
+    windows: Arc<DashMap<IpAddr, Window>>,
+}
+
+impl RateLimiter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Counts a request from `client` against `per_minute` (0 allows everything). On
+    /// refusal, returns how long until the client's window resets.
+    pub fn check(&self, client: IpAddr, per_minute: u32) -> Result<(), Duration> {
+        if per_minute == 0 {
+            return Ok(());
+        }
+        let now = Instant::now();
+        let mut window = self.windows.entry(client).or_insert(Window {
+            started: now,
+            requests: 0,
+        });
+        if now.duration_since(window.started) >= WINDOW {
+            *window = Window {
+                started: now,
+                requests: 0,
+            };
+        }
+        if window.requests >= per_minute {
+            return Err(WINDOW.saturating_sub(now.duration_since(window.started)));
+        }
+        window.requests += 1;
+        Ok(())
+    }
+
+    /// Forgets clients whose window has ended; returns how many were dropped.
+    pub fn prune(&self) -> usize {
+        let before = self.windows.len();
+        self.windows
+            .retain(|_, window| window.started.elapsed() < WINDOW);
+        before.saturating_sub(self.windows.len())
+    }
+
+    /// Clients with an open window.
+    pub fn tracked_clients(&self) -> usize {
+        self.windows.len()
+    }
+}
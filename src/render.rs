@@ -0,0 +1,318 @@
+//! Visual treatment of slider pieces and the holes they were cut from.
+//!
+//! The hole is drawn opaque: its pixels are darkened in place rather than made
+//! transparent, so the original colours never leak through the alpha channel.
+//!
+//! At higher [`Difficulty`] levels the piece texture is also perturbed independently
+//! of the background, so a pixel-exact template match against the hole fails.
+
+use image::{imageops, Rgba, RgbaImage};
+use rand::Rng;
+
+use crate::site::DEFAULT_TOLERANCE;
+
+/// Width of the inset shadow along the top and left edges of a hole, in pixels.
+const INSET_WIDTH: f32 = 6.0;
+/// Blur radius of the piece's drop shadow, in pixels.
+const SHADOW_BLUR: u32 = 4;
+/// Drop shadow offset towards the bottom right, in pixels.
+const SHADOW_OFFSET: u32 = 2;
+
+/// Colours and strengths used to render pieces and holes; strengths are `0.0..=1.0`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PieceStyle {
+    pub outline_colour: [u8; 3],
+    /// Outline thickness in pixels; `0` disables it.
+    pub outline_width: u32,
+    /// Highlight along the piece's top and left inner edges; the bottom and right
+    /// edges are darkened by half as much, giving a bevel.
+    pub glow_colour: [u8; 3],
+    pub glow_strength: f32,
+    /// Drop shadow behind the piece. A non-zero strength pads the piece image, see
+    /// [`PieceStyle::padding`].
+    pub shadow_colour: [u8; 3],
+    pub shadow_strength: f32,
+    /// How much darker the hole is than the surrounding background, including its
+    /// inset shadow.
+    pub hole_shade: f32,
+}
+
+impl Default for PieceStyle {
+    fn default() -> Self {
+        Self {
+            outline_colour: [255, 255, 255],
+            outline_width: 2,
+            glow_colour: [255, 255, 255],
+            glow_strength: 0.35,
+            shadow_colour: [0, 0, 0],
+            shadow_strength: 0.0,
+            hole_shade: 0.5,
+        }
+    }
+}
+
+impl PieceStyle {
+    /// Transparent border added around every piece image to make room for the shadow.
+    pub fn padding(&self) -> u32 {
+        if self.shadow_strength > 0.0 {
+            SHADOW_BLUR + SHADOW_OFFSET
+        } else {
+            0
+        }
+    }
+}
+
+/// How hard the piece is to match against its hole by pixel comparison.
+#[derive(
+    Clone, Copy, Debug, Default, PartialEq, Eq, Hash, serde::Serialize, serde::Deserialize,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum Difficulty {
+    /// The piece is an exact copy of the hole's pixels.
+    #[default]
+    Off,
+    Easy,
+    Normal,
+    Hard,
+}
+
+impl Difficulty {
+    pub const ALL: [Difficulty; 4] = [
+        Difficulty::Off,
+        Difficulty::Easy,
+        Difficulty::Normal,
+        Difficulty::Hard,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Difficulty::Off => "off",
+            Difficulty::Easy => "easy",
+            Difficulty::Normal => "normal",
+            Difficulty::Hard => "hard",
+        }
+    }
+
+    pub fn parse(raw: &str) -> Option<Self> {
+        let raw = raw.trim();
+        Self::ALL
+            .into_iter()
+            .find(|difficulty| difficulty.name().eq_ignore_ascii_case(raw))
+    }
+
+    pub fn perturbation(self) -> Perturbation {
+        match self {
+            Difficulty::Off => Perturbation::default(),
+            Difficulty::Easy => Perturbation {
+                texture_offset: 1,
+                scale: 0.02,
+                recolour: 0.04,
+                noise: 4,
+                blur: 0.0,
+            },
+            Difficulty::Normal => Perturbation {
+                texture_offset: 2,
+                scale: 0.04,
+                recolour: 0.08,
+                noise: 8,
+                blur: 0.6,
+            },
+            Difficulty::Hard => Perturbation {
+                texture_offset: 3,
+                scale: 0.07,
+                recolour: 0.12,
+                noise: 14,
+                blur: 1.0,
+            },
+        }
+    }
+}
+
+/// Upper bounds for the random changes applied to a piece's texture. All zero disables
+/// the stage and draws nothing from the RNG.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Perturbation {
+    /// The texture is sampled up to this many pixels away from the hole, per axis.
+    /// Capped per puzzle by [`Perturbation::within_margin`].
+    pub texture_offset: u32,
+    /// Zoom applied around the piece centre, as a fraction (`0.05` = up to ±5%).
+    pub scale: f32,
+    /// Per-channel gain change, as a fraction.
+    pub recolour: f32,
+    /// Per-pixel uniform noise amplitude, in 0-255 channel units.
+    pub noise: u8,
+    /// Gaussian blur sigma in pixels.
+    pub blur: f32,
+}
+
+impl Perturbation {
+    pub fn is_active(&self) -> bool {
+        self.texture_offset > 0
+            || self.scale > 0.0
+            || self.recolour > 0.0
+            || self.noise > 0
+            || self.blur > 0.0
+    }
+
+    /// Caps the texture offset and zoom for an image `span` pixels long along the moving
+    /// axis with a piece `piece` pixels long, so a piece lined up on its texture instead of
+    /// its outline still lands within [`DEFAULT_TOLERANCE`]. Each gets half the margin; the
+    /// zoom is measured at the piece's edge.
+    pub fn within_margin(self, span: u32, piece: u32) -> Self {
+        let budget = DEFAULT_TOLERANCE * span as f64 / 2.0;
+        Self {
+            texture_offset: self.texture_offset.min(budget.floor() as u32),
+            scale: self
+                .scale
+                .min((budget / (piece.max(1) as f64 / 2.0)) as f32),
+            ..self
+        }
+    }
+}
+
+/// Re-samples the piece at `origin` from `background` with a random offset, zoom,
+/// colour gain and noise, so its pixels no longer equal the hole's.
+pub(crate) fn perturb_piece<R: Rng + ?Sized>(
+    background: &RgbaImage,
+    origin: (u32, u32),
+    size: (u32, u32),
+    perturbation: &Perturbation,
+    rng: &mut R,
+) -> RgbaImage {
+    let offset = perturbation.texture_offset as f32;
+    let shift_x = rng.gen_range(-offset..=offset);
+    let shift_y = rng.gen_range(-offset..=offset);
+    let zoom = 1.0 + rng.gen_range(-perturbation.scale..=perturbation.scale);
+    let gain: [f32; 3] = [
+        1.0 + rng.gen_range(-perturbation.recolour..=perturbation.recolour),
+        1.0 + rng.gen_range(-perturbation.recolour..=perturbation.recolour),
+        1.0 + rng.gen_range(-perturbation.recolour..=perturbation.recolour),
+    ];
+    let noise = perturbation.noise as i16;
+
+    let (width, height) = background.dimensions();
+    let (centre_x, centre_y) = (size.0 as f32 / 2.0, size.1 as f32 / 2.0);
+    let max_x = (width - 1) as f32;
+    let max_y = (height - 1) as f32;
+
+    let mut piece = RgbaImage::new(size.0, size.1);
+    for (x, y, pixel) in piece.enumerate_pixels_mut() {
+        let source_x = origin.0 as f32 + centre_x + (x as f32 - centre_x) / zoom + shift_x;
+        let source_y = origin.1 as f32 + centre_y + (y as f32 - centre_y) / zoom + shift_y;
+        let sample = background.get_pixel(
+            source_x.round().clamp(0.0, max_x) as u32,
+            source_y.round().clamp(0.0, max_y) as u32,
+        );
+
+        let mut channels = [0u8; 4];
+        for channel in 0..3 {
+            let jitter = if noise > 0 {
+                rng.gen_range(-noise..=noise)
+            } else {
+                0
+            };
+            let value = sample[channel] as f32 * gain[channel] + jitter as f32;
+            channels[channel] = value.clamp(0.0, 255.0) as u8;
+        }
+        channels[3] = 255;
+        *pixel = Rgba(channels);
+    }
+
+    if perturbation.blur > 0.0 {
+        imageops::blur(&piece, perturbation.blur)
+    } else {
+        piece
+    }
+}
+
+/// Parses `#rrggbb` or `rrggbb`.
+pub fn parse_colour(raw: &str) -> Option<[u8; 3]> {
+    let hex = raw.trim().trim_start_matches('#');
+    if hex.len() != 6 || !hex.is_ascii() {
+        return None;
+    }
+    let channel = |at: usize| u8::from_str_radix(&hex[at..at + 2], 16).ok();
+    Some([channel(0)?, channel(2)?, channel(4)?])
+}
+
+fn mix(from: [u8; 3], to: [u8; 3], t: f32) -> [u8; 3] {
+    let t = t.clamp(0.0, 1.0);
+    let channel = |c: usize| (from[c] as f32 + (to[c] as f32 - from[c] as f32) * t) as u8;
+    [channel(0), channel(1), channel(2)]
+}
+
+/// Renders a raw piece cut-out with outline, bevel glow and optional drop shadow.
+pub(crate) fn style_piece(source: &RgbaImage, style: &PieceStyle) -> RgbaImage {
+    let (width, height) = source.dimensions();
+    let padding = style.padding();
+    let mut piece = RgbaImage::new(width + 2 * padding, height + 2 * padding);
+
+    if padding > 0 {
+        let (left, top) = (
+            (padding + SHADOW_OFFSET) as f32,
+            (padding + SHADOW_OFFSET) as f32,
+        );
+        let (right, bottom) = (left + width as f32 - 1.0, top + height as f32 - 1.0);
+        let [r, g, b] = style.shadow_colour;
+        for (x, y, pixel) in piece.enumerate_pixels_mut() {
+            let dx = (left - x as f32).max(x as f32 - right).max(0.0);
+            let dy = (top - y as f32).max(y as f32 - bottom).max(0.0);
+            let fade = 1.0 - (dx * dx + dy * dy).sqrt() / SHADOW_BLUR as f32;
+            if fade > 0.0 {
+                let alpha = (style.shadow_strength.clamp(0.0, 1.0) * fade * 255.0) as u8;
+                *pixel = Rgba([r, g, b, alpha]);
+            }
+        }
+    }
+
+    let glow_width = (width.min(height) / 8).max(2) as f32;
+    for (x, y, source_pixel) in source.enumerate_pixels() {
+        let base = [source_pixel[0], source_pixel[1], source_pixel[2]];
+        let near = x.min(y);
+        let far = (width - 1 - x).min(height - 1 - y);
+        let edge = near.min(far);
+
+        let colour = if edge < style.outline_width {
+            mix(base, style.outline_colour, 0.9)
+        } else {
+            let depth = (edge - style.outline_width) as f32;
+            let strength = style.glow_strength * (1.0 - depth / glow_width).max(0.0);
+            if near <= far {
+                mix(base, style.glow_colour, strength)
+            } else {
+                mix(base, [0, 0, 0], strength * 0.5)
+            }
+        };
+
+        let [r, g, b] = colour;
+        piece.put_pixel(x + padding, y + padding, Rgba([r, g, b, 255]));
+    }
+
+    piece
+}
+
+/// Darkens the hole at `origin` in place, with an inset shadow along its top and left.
+pub(crate) fn shade_hole(
+    image: &mut RgbaImage,
+    origin: (u32, u32),
+    size: (u32, u32),
+    style: &PieceStyle,
+) {
+    let shade = style.hole_shade.clamp(0.0, 1.0);
+    let row_len = image.width() as usize * 4;
+    let pixels: &mut [u8] = image;
+
+    for dy in 0..size.1 {
+        let y = (origin.1 + dy) as usize;
+        let row = &mut pixels[y * row_len..(y + 1) * row_len];
+        for dx in 0..size.0 {
+            let inset = (1.0 - dx.min(dy) as f32 / INSET_WIDTH).max(0.0);
+            let factor = 1.0 - shade * (0.6 + 0.4 * inset);
+            let x = (origin.0 + dx) as usize * 4;
+            for channel in &mut row[x..x + 3] {
+                *channel = (*channel as f32 * factor) as u8;
+            }
+            row[x + 3] = 255;
+        }
+    }
+}
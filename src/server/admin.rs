@@ -0,0 +1,378 @@
+//! Authenticated `/admin` endpoints for operators.
+//!
+//! Every route needs `Authorization: Bearer <admin_token>` and answers 401 `UNAUTHORIZED`
+//! while no token is configured. Settings changed through `/admin/settings` last until the
+//! next reload re-reads the configuration sources.
+
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use actix_web::{delete, get, http::header, patch, post, web, HttpRequest, HttpResponse, Scope};
+use tokio::task::spawn_blocking;
+use tracing::{info, warn};
+
+use super::{challenge_key, reload_config, ApiError, AppState, ErrorCode};
+use crate::{
+    api::{default_height, default_icon_count, default_piece_count, default_width},
+    config::AppConfig,
+    generator::{Answer, ChallengeKind, ImageEncoding, PuzzleKey},
+    puzzle::Orientation,
+    render::Difficulty,
+    site::{secrets_match, ChallengeType},
+};
+
+pub(super) fn scope() -> Scope {
+    web::scope("/admin")
+        .service(reload_handler)
+        .service(cache_handler)
+        .service(refill_handler)
+        .service(flush_handler)
+        .service(solution_handler)
+        .service(revoke_handler)
+        .service(settings_handler)
+        .service(update_settings_handler)
+}
+
+/// Checks `Authorization: Bearer <admin_token>`; admin routes are off without a token.
+fn admin_authorized(req: &HttpRequest, config: &AppConfig) -> bool {
+    let Some(token) = config.admin_token.as_deref() else {
+        return false;
+    };
+    let presented = req
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .unwrap_or_default();
+    secrets_match(token, presented)
+}
+
+#[post("/reload")]
+async fn reload_handler(
+    req: HttpRequest,
+    state: web::Data<AppState>,
+) -> Result<HttpResponse, ApiError> {
+    if !admin_authorized(&req, &state.config.load()) {
+        return Err(ApiError::unauthorized());
+    }
+    let reload_state = state.get_ref().clone();
+    match spawn_blocking(move || reload_config(&reload_state)).await {
+        Ok(Ok(restart_required)) => Ok(HttpResponse::Ok().json(serde_json::json!({
+            "reloaded": true,
+            "restart_required": restart_required,
+        }))),
+        Ok(Err(err)) => {
+            warn!(error = %err, "Configuration reload rejected");
+            Err(ApiError::new(ErrorCode::InvalidConfig, err.to_string()))
+        }
+        Err(err) => {
+            tracing::error!(error = ?err, "Configuration reload panicked");
+            Err(ApiError::internal())
+        }
+    }
+}
+
+/// Selects one cache bucket with the same parameters as `/puzzle`; `format` and
+/// `difficulty` default to the configured ones.
+#[derive(serde::Deserialize)]
+struct BucketQuery {
+    #[serde(default = "default_width")]
+    w: u32,
+    #[serde(default = "default_height")]
+    h: u32,
+    #[serde(default, rename = "type")]
+    kind: ChallengeType,
+    #[serde(default = "default_icon_count")]
+    n: u8,
+    #[serde(default = "default_piece_count")]
+    pieces: u8,
+    #[serde(default)]
+    orientation: Orientation,
+    #[serde(default)]
+    format: Option<String>,
+    #[serde(default)]
+    difficulty: Option<String>,
+    /// Refill target; defaults to the prefill count.
+    #[serde(default)]
+    count: Option<usize>,
+}
+
+impl BucketQuery {
+    fn key(&self, config: &AppConfig) -> Result<PuzzleKey, ApiError> {
+        let encoding = match self.format.as_deref() {
+            Some(raw) => ImageEncoding::parse(raw)
+                .ok_or_else(|| ApiError::invalid_request(format!("unknown format {raw:?}")))?,
+            None => config.image_encoding,
+        };
+        let difficulty = match self.difficulty.as_deref() {
+            Some(raw) => Difficulty::parse(raw)
+                .ok_or_else(|| ApiError::invalid_request(format!("unknown difficulty {raw:?}")))?,
+            None => config.difficulty,
+        };
+        let key = challenge_key(
+            self.kind,
+            self.w,
+            self.h,
+            self.n,
+            self.pieces,
+            self.orientation,
+        )
+        .with_encoding(encoding)
+        .with_difficulty(difficulty);
+        key.validate()?;
+        Ok(key)
+    }
+}
+
+fn bucket_json(key: &PuzzleKey, cached: usize) -> serde_json::Value {
+    let mut bucket = serde_json::json!({
+        "size": format!("{}x{}", key.width, key.height),
+        "format": key.encoding.name(),
+        "difficulty": key.difficulty.name(),
+        "cached": cached,
+    });
+    match key.kind {
+        ChallengeKind::Slider {
+            pieces,
+            orientation,
+        } => {
+            bucket["type"] = "slider".into();
+            bucket["pieces"] = pieces.into();
+            bucket["orientation"] = serde_json::json!(orientation);
+        }
+        ChallengeKind::Click { icons } => {
+            bucket["type"] = "click".into();
+            bucket["n"] = icons.into();
+        }
+    }
+    bucket
+}
+
+/// Every cache bucket, including those filled on demand, with its live entry count.
+#[get("/cache")]
+async fn cache_handler(
+    req: HttpRequest,
+    state: web::Data<AppState>,
+) -> Result<HttpResponse, ApiError> {
+    let config = state.config.load();
+    if !admin_authorized(&req, &config) {
+        return Err(ApiError::unauthorized());
+    }
+    let mut levels = state.generator.cache_levels();
+    levels.sort_by_key(|(key, _)| (key.width, key.height, format!("{key:?}")));
+    let buckets: Vec<_> = levels
+        .iter()
+        .map(|(key, cached)| bucket_json(key, *cached))
+        .collect();
+
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "buckets": buckets,
+        "total": state.generator.total_cached(),
+        "prefill": config.cache_prefill_per_size,
+        "max_per_bucket": config.cache_max_per_size,
+        "queue_depth": state.generator.queue_depth(),
+    })))
+}
+
+#[post("/cache/refill")]
+async fn refill_handler(
+    req: HttpRequest,
+    state: web::Data<AppState>,
+    query: web::Query<BucketQuery>,
+) -> Result<HttpResponse, ApiError> {
+    let config = state.config.load();
+    if !admin_authorized(&req, &config) {
+        return Err(ApiError::unauthorized());
+    }
+    let key = query.key(&config)?;
+    let target = query
+        .count
+        .unwrap_or(config.cache_prefill_per_size)
+        .min(config.cache_max_per_size);
+
+    let queued = state.generator.refill(key, target)?;
+    info!(key = ?key, queued, "Cache refill requested");
+    Ok(HttpResponse::Accepted().json(serde_json::json!({
+        "bucket": bucket_json(&key, state.generator.cache_len(&key)),
+        "queued": queued,
+    })))
+}
+
+#[delete("/cache")]
+async fn flush_handler(
+    req: HttpRequest,
+    state: web::Data<AppState>,
+    query: web::Query<BucketQuery>,
+) -> Result<HttpResponse, ApiError> {
+    let config = state.config.load();
+    if !admin_authorized(&req, &config) {
+        return Err(ApiError::unauthorized());
+    }
+    let key = query.key(&config)?;
+    let flushed = state.generator.flush(&key);
+    info!(key = ?key, flushed, "Cache bucket flushed");
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "bucket": bucket_json(&key, 0),
+        "flushed": flushed,
+    })))
+}
+
+#[derive(serde::Deserialize)]
+struct SolutionQuery {
+    /// Include the expected answer in the response.
+    #[serde(default)]
+    reveal: bool,
+}
+
+#[get("/solutions/{id}")]
+async fn solution_handler(
+    req: HttpRequest,
+    state: web::Data<AppState>,
+    id: web::Path<String>,
+    query: web::Query<SolutionQuery>,
+) -> Result<HttpResponse, ApiError> {
+    let config = state.config.load();
+    if !admin_authorized(&req, &config) {
+        return Err(ApiError::unauthorized());
+    }
+    let id = id.into_inner();
+    let Some(entry) = state.generator.get_solution(&id) else {
+        return Err(ApiError::new(ErrorCode::InvalidId, "Unknown solution id"));
+    };
+
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs();
+    let status = if entry.expires_at <= now {
+        "expired"
+    } else if entry.verified {
+        "solved"
+    } else {
+        "pending"
+    };
+    let kind = match entry.answer {
+        Answer::Slider { .. } => "slider",
+        Answer::Click { .. } => "click",
+    };
+    let mut body = serde_json::json!({
+        "id": id,
+        "status": status,
+        "type": kind,
+        "attempts": entry.attempts,
+        "remaining_attempts": config.max_attempts.saturating_sub(entry.attempts),
+        "expires_at": entry.expires_at,
+        "site": entry.site.as_ref().map(|site| site.site_key.as_str()),
+        "images_held": entry.images.is_some(),
+    });
+    if query.reveal {
+        warn!(%id, "Solution answer revealed through the admin API");
+        body["answer"] = serde_json::json!(entry.answer);
+    }
+    Ok(HttpResponse::Ok().json(body))
+}
+
+#[derive(serde::Deserialize)]
+struct RevokePayload {
+    ids: Vec<String>,
+}
+
+/// Discards solutions so their ids can no longer be verified or redeemed.
+#[post("/revoke")]
+async fn revoke_handler(
+    req: HttpRequest,
+    state: web::Data<AppState>,
+    payload: web::Json<RevokePayload>,
+) -> Result<HttpResponse, ApiError> {
+    if !admin_authorized(&req, &state.config.load()) {
+        return Err(ApiError::unauthorized());
+    }
+    let (revoked, unknown): (Vec<&String>, Vec<&String>) = payload
+        .ids
+        .iter()
+        .partition(|id| state.generator.remove_solution(id).is_some());
+    info!(
+        revoked = revoked.len(),
+        unknown = unknown.len(),
+        "Solutions revoked"
+    );
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "revoked": revoked,
+        "unknown": unknown,
+    })))
+}
+
+fn settings_json(config: &AppConfig, state: &AppState) -> serde_json::Value {
+    serde_json::json!({
+        "difficulty": config.difficulty.name(),
+        "max_attempts": config.max_attempts,
+        "rate_limit_per_minute": config.rate_limit_per_minute,
+        "rate_limited_clients": state.rate_limiter.tracked_clients(),
+    })
+}
+
+#[get("/settings")]
+async fn settings_handler(
+    req: HttpRequest,
+    state: web::Data<AppState>,
+) -> Result<HttpResponse, ApiError> {
+    let config = state.config.load();
+    if !admin_authorized(&req, &config) {
+        return Err(ApiError::unauthorized());
+    }
+    Ok(HttpResponse::Ok().json(settings_json(&config, &state)))
+}
+
+#[derive(serde::Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SettingsPatch {
+    #[serde(default)]
+    difficulty: Option<String>,
+    #[serde(default)]
+    max_attempts: Option<u32>,
+    #[serde(default)]
+    rate_limit_per_minute: Option<u32>,
+}
+
+#[patch("/settings")]
+async fn update_settings_handler(
+    req: HttpRequest,
+    state: web::Data<AppState>,
+    payload: web::Json<SettingsPatch>,
+) -> Result<HttpResponse, ApiError> {
+    if !admin_authorized(&req, &state.config.load()) {
+        return Err(ApiError::unauthorized());
+    }
+    let difficulty = match payload.difficulty.as_deref().map(Difficulty::parse) {
+        Some(None) => {
+            return Err(ApiError::invalid_request(
+                "difficulty must be off, easy, normal or hard",
+            ))
+        }
+        Some(Some(difficulty)) => Some(difficulty),
+        None => None,
+    };
+    if payload.max_attempts == Some(0) {
+        return Err(ApiError::invalid_request("max_attempts must be positive"));
+    }
+
+    let config = state.config.update(|config| {
+        if let Some(difficulty) = difficulty {
+            config.difficulty = difficulty;
+        }
+        if let Some(max_attempts) = payload.max_attempts {
+            config.max_attempts = max_attempts;
+        }
+        if let Some(rate_limit) = payload.rate_limit_per_minute {
+            config.rate_limit_per_minute = rate_limit;
+        }
+    });
+    // A new difficulty is a new set of prefill buckets.
+    state.generator.fill_cache(&config);
+    info!(
+        difficulty = config.difficulty.name(),
+        max_attempts = config.max_attempts,
+        rate_limit_per_minute = config.rate_limit_per_minute,
+        "Runtime settings changed"
+    );
+    Ok(HttpResponse::Ok().json(settings_json(&config, &state)))
+}
@@ -0,0 +1,152 @@
+//! The error envelope shared by every endpoint.
+//!
+//! ```json
+//! {
+//!   "success": false,
+//!   "version": 1,
+//!   "code": "WRONG_ANSWER",
+//!   "error": "Verification failed",
+//!   "details": { "attempts": 1, "remaining": 4 }
+//! }
+//! ```
+//!
+//! Clients switch on `code`, whose values are stable. `error` is a human-readable
+//! message that may change, `details` is only present for codes that carry extra data,
+//! and `version` changes only if the shape of the envelope does.
+
+use std::fmt;
+
+use actix_web::{
+    http::{header, StatusCode},
+    HttpResponse, ResponseError,
+};
+
+use crate::{
+    api::{ErrorCode, ErrorResponse, ERROR_ENVELOPE_VERSION},
+    error::PuzzleError,
+};
+
+/// The status a response with `code` is sent with.
+fn status_for(code: ErrorCode) -> StatusCode {
+    match code {
+        ErrorCode::InvalidRequest
+        | ErrorCode::NotAllowedForSite
+        | ErrorCode::NotVerified
+        | ErrorCode::InvalidConfig => StatusCode::BAD_REQUEST,
+        ErrorCode::Unauthorized | ErrorCode::InvalidSecret => StatusCode::UNAUTHORIZED,
+        ErrorCode::SiteKeyRequired | ErrorCode::InvalidSiteKey | ErrorCode::OriginNotAllowed => {
+            StatusCode::FORBIDDEN
+        }
+        ErrorCode::InvalidId | ErrorCode::NotFound => StatusCode::NOT_FOUND,
+        ErrorCode::Expired | ErrorCode::TooManyAttempts => StatusCode::GONE,
+        ErrorCode::WrongAnswer => StatusCode::UNPROCESSABLE_ENTITY,
+        ErrorCode::RateLimited => StatusCode::TOO_MANY_REQUESTS,
+        ErrorCode::GenerationFailed | ErrorCode::Internal => StatusCode::INTERNAL_SERVER_ERROR,
+        ErrorCode::Unavailable | ErrorCode::ShuttingDown => StatusCode::SERVICE_UNAVAILABLE,
+    }
+}
+
+/// A failed request, rendered as the envelope described in the module docs.
+#[derive(Clone, Debug)]
+pub struct ApiError {
+    pub code: ErrorCode,
+    pub message: String,
+    pub details: Option<serde_json::Value>,
+    /// Seconds for the `Retry-After` header.
+    pub retry_after: Option<u64>,
+    status: StatusCode,
+}
+
+impl ApiError {
+    pub fn new(code: ErrorCode, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            message: message.into(),
+            details: None,
+            retry_after: None,
+            status: status_for(code),
+        }
+    }
+
+    pub fn with_details(self, details: serde_json::Value) -> Self {
+        Self {
+            details: Some(details),
+            ..self
+        }
+    }
+
+    pub fn with_retry_after(self, secs: u64) -> Self {
+        Self {
+            retry_after: Some(secs),
+            ..self
+        }
+    }
+
+    pub fn invalid_request(message: impl Into<String>) -> Self {
+        Self::new(ErrorCode::InvalidRequest, message)
+    }
+
+    pub fn unauthorized() -> Self {
+        Self::new(ErrorCode::Unauthorized, "Unauthorized")
+    }
+
+    pub fn internal() -> Self {
+        Self::new(ErrorCode::Internal, "Internal server error")
+    }
+
+    /// The JSON body sent for this error.
+    pub fn body(&self) -> ErrorResponse {
+        ErrorResponse {
+            success: false,
+            version: ERROR_ENVELOPE_VERSION,
+            code: self.code,
+            error: self.message.clone(),
+            details: self.details.clone(),
+        }
+    }
+}
+
+impl fmt::Display for ApiError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.code, self.message)
+    }
+}
+
+impl std::error::Error for ApiError {}
+
+impl ResponseError for ApiError {
+    fn status_code(&self) -> StatusCode {
+        self.status
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        let mut response = HttpResponse::build(self.status);
+        if let Some(secs) = self.retry_after {
+            response.insert_header((header::RETRY_AFTER, secs.to_string()));
+        }
+        response.json(self.body())
+    }
+}
+
+impl From<PuzzleError> for ApiError {
+    fn from(err: PuzzleError) -> Self {
+        match err {
+            PuzzleError::InvalidDimensions { .. } | PuzzleError::InvalidLayout(_) => {
+                ApiError::invalid_request(err.to_string())
+            }
+            // The background source is an upstream of ours.
+            PuzzleError::Background(_) => ApiError {
+                status: StatusCode::BAD_GATEWAY,
+                ..ApiError::new(ErrorCode::GenerationFailed, err.to_string())
+            },
+            PuzzleError::Encode(_) => ApiError::new(ErrorCode::GenerationFailed, err.to_string()),
+            PuzzleError::Unavailable => ApiError::new(
+                ErrorCode::Unavailable,
+                "Puzzle generation busy, try again later",
+            ),
+            PuzzleError::ShuttingDown => {
+                ApiError::new(ErrorCode::ShuttingDown, "Server is shutting down")
+            }
+        }
+    }
+}
@@ -0,0 +1,972 @@
+use std::{
+    future::Future,
+    net::TcpListener,
+    sync::Arc,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use actix_web::{
+    get,
+    http::header,
+    rt::{spawn, time},
+    web, App, HttpRequest, HttpResponse, HttpServer, Responder,
+};
+use tokio::task::spawn_blocking;
+use tracing::{info, warn};
+
+use crate::{
+    api::{
+        ErrorResponse, PieceImage, PuzzleQuery, PuzzleResponse, SiteVerifyRequest,
+        SiteVerifyResponse, SolutionRequest, VerifyResponse,
+    },
+    click::verify_clicks,
+    config::{AppConfig, ConfigError, ImageDelivery, SharedConfig},
+    generator::{
+        bytes_to_base64, Answer, Challenge, ImageEncoding, ImageFormat, PiecePosition,
+        PuzzleGenerator, PuzzleImages, PuzzleKey,
+    },
+    puzzle::{verify_pieces, Orientation},
+    rate_limit::RateLimiter,
+    site::{ChallengeType, SiteConfig, DEFAULT_TOLERANCE},
+};
+
+mod admin;
+mod error;
+mod openapi;
+
+pub use crate::api::{ErrorCode, API_V1, ERROR_ENVELOPE_VERSION};
+pub use error::ApiError;
+pub use openapi::ApiDoc;
+
+#[derive(Clone)]
+pub struct AppState {
+    pub generator: Arc<PuzzleGenerator>,
+    /// Take a snapshot with `config.load()` once per request.
+    pub config: SharedConfig,
+    pub rate_limiter: RateLimiter,
+}
+
+fn solution_matches(solution: &SolutionRequest, answer: &Answer, tolerance: f64) -> bool {
+    match (answer, &solution.points) {
+        (
+            Answer::Slider {
+                targets,
+                orientation,
+            },
+            _,
+        ) => {
+            let submitted = match orientation {
+                Orientation::Horizontal => &solution.x,
+                Orientation::Vertical => &solution.y,
+            };
+            submitted
+                .as_ref()
+                .is_some_and(|values| verify_pieces(targets, values.values(), tolerance))
+        }
+        (Answer::Click { points, tolerance }, Some(submitted)) => {
+            let submitted: Vec<(f64, f64)> =
+                submitted.iter().map(|point| (point.x, point.y)).collect();
+            verify_clicks(points, &submitted, *tolerance)
+        }
+        _ => false,
+    }
+}
+
+/// Horizontal pieces report their fixed `y`; vertical pieces report their fixed `x`.
+fn fixed_coordinate(orientation: Orientation, piece: &PiecePosition) -> (Option<f64>, Option<f64>) {
+    match orientation {
+        Orientation::Horizontal => (None, Some(piece.y)),
+        Orientation::Vertical => (Some(piece.x), None),
+    }
+}
+
+/// One image of a `/puzzle` response, inline or as a binary-endpoint URL.
+struct EncodedImage {
+    image: Option<String>,
+    url: Option<String>,
+    mime: String,
+}
+
+/// Encodes the images of a `/puzzle` response for the configured delivery.
+struct ImageFields<'a> {
+    delivery: ImageDelivery,
+    id: &'a str,
+    /// Path prefix of the route the puzzle was requested on, so URLs stay on that version.
+    base: &'a str,
+}
+
+impl ImageFields<'_> {
+    fn encode(&self, asset: &str, format: ImageFormat, bytes: &[u8]) -> EncodedImage {
+        let (image, url) = match self.delivery {
+            ImageDelivery::Inline => (Some(bytes_to_base64(bytes)), None),
+            ImageDelivery::Url => {
+                let url = format!(
+                    "{}/puzzle/{}/{asset}.{}",
+                    self.base,
+                    self.id,
+                    format.extension()
+                );
+                (None, Some(url))
+            }
+        };
+        EncodedImage {
+            image,
+            url,
+            mime: format.mime().to_string(),
+        }
+    }
+}
+
+fn puzzle_response(images: &PuzzleImages, fields: &ImageFields) -> PuzzleResponse {
+    let background = images.encoding.background();
+    let piece_format = images.encoding.piece();
+
+    let puzzle = fields.encode("background", background, &images.background);
+    let mut response = PuzzleResponse {
+        id: fields.id.to_string(),
+        puzzle_image: puzzle.image,
+        puzzle_url: puzzle.url,
+        puzzle_mime: puzzle.mime,
+        ..PuzzleResponse::default()
+    };
+
+    match &images.challenge {
+        Challenge::Slider {
+            pieces,
+            orientation,
+            ..
+        } if pieces.len() == 1 => {
+            let piece = fields.encode("piece", piece_format, &images.pieces[0]);
+            response.piece_image = piece.image;
+            response.piece_url = piece.url;
+            response.piece_mime = Some(piece.mime);
+            (response.x, response.y) = fixed_coordinate(*orientation, &pieces[0]);
+        }
+        Challenge::Slider {
+            pieces,
+            orientation,
+            ..
+        } => {
+            let pieces = pieces
+                .iter()
+                .zip(&images.pieces)
+                .enumerate()
+                .map(|(index, (piece, image))| {
+                    let encoded = fields.encode(&format!("piece-{index}"), piece_format, image);
+                    let (x, y) = fixed_coordinate(*orientation, piece);
+                    PieceImage {
+                        image: encoded.image,
+                        url: encoded.url,
+                        mime: encoded.mime,
+                        x,
+                        y,
+                    }
+                })
+                .collect();
+            response.pieces = Some(pieces);
+        }
+        Challenge::Click { icons, .. } => {
+            let hint = fields.encode("hint", piece_format, &images.pieces[0]);
+            response.kind = Some(ChallengeType::Click);
+            response.icons = Some(icons.iter().map(|icon| icon.name().to_string()).collect());
+            response.hint_image = hint.image;
+            response.hint_url = hint.url;
+            response.hint_mime = Some(hint.mime);
+        }
+    }
+
+    if let Challenge::Slider {
+        orientation,
+        padding,
+        ..
+    } = &images.challenge
+    {
+        if *orientation == Orientation::Vertical {
+            response.orientation = Some(*orientation);
+        }
+        if *padding > 0 {
+            response.piece_padding = Some(*padding);
+        }
+    }
+
+    response
+}
+
+/// The cache bucket a challenge request draws from, before encoding and difficulty. Sides
+/// below 100 pixels are raised to 100.
+pub(super) fn challenge_key(
+    kind: ChallengeType,
+    width: u32,
+    height: u32,
+    icons: u8,
+    pieces: u8,
+    orientation: Orientation,
+) -> PuzzleKey {
+    let (width, height) = (width.max(100), height.max(100));
+    match kind {
+        ChallengeType::Slider => PuzzleKey::slider_variant(width, height, pieces, orientation),
+        ChallengeType::Click => PuzzleKey::click(width, height, icons),
+    }
+}
+
+/// Looks up the site named by `sitekey` and checks the request against its restrictions.
+fn resolve_site(
+    req: &HttpRequest,
+    config: &AppConfig,
+    query: &PuzzleQuery,
+    width: u32,
+    height: u32,
+) -> Result<Option<Arc<SiteConfig>>, ApiError> {
+    let Some(site_key) = query.sitekey.as_deref() else {
+        return if config.require_site_key {
+            Err(ApiError::new(ErrorCode::SiteKeyRequired, "Missing sitekey"))
+        } else {
+            Ok(None)
+        };
+    };
+    let site = config
+        .sites
+        .get(site_key)
+        .ok_or_else(|| ApiError::new(ErrorCode::InvalidSiteKey, "Unknown sitekey"))?;
+
+    let origin = req
+        .headers()
+        .get(header::ORIGIN)
+        .and_then(|value| value.to_str().ok());
+    if !site.allows_origin(origin) {
+        return Err(ApiError::new(
+            ErrorCode::OriginNotAllowed,
+            "Origin not allowed for this site",
+        ));
+    }
+    if !site.allows_type(query.kind) {
+        return Err(ApiError::new(
+            ErrorCode::NotAllowedForSite,
+            "Challenge type not allowed for this site",
+        ));
+    }
+    if !site.allows_size(width, height) {
+        return Err(ApiError::new(
+            ErrorCode::NotAllowedForSite,
+            "Puzzle size not allowed for this site",
+        ));
+    }
+    Ok(Some(site.clone()))
+}
+
+/// The prefix of the route a request came in on, so returned URLs stay on that version.
+fn api_base(req: &HttpRequest) -> &'static str {
+    match req.path().strip_prefix(API_V1) {
+        Some(rest) if rest.starts_with('/') => API_V1,
+        _ => "",
+    }
+}
+
+/// Issues a new challenge.
+#[utoipa::path(
+    get,
+    path = "/v1/puzzle",
+    params(PuzzleQuery),
+    responses(
+        (status = 200, body = PuzzleResponse),
+        (status = 400, description = "`INVALID_REQUEST` or `NOT_ALLOWED_FOR_SITE`", body = ErrorResponse),
+        (status = 403, description = "`SITE_KEY_REQUIRED`, `INVALID_SITE_KEY` or `ORIGIN_NOT_ALLOWED`", body = ErrorResponse),
+        (status = 429, description = "`RATE_LIMITED`", body = ErrorResponse,
+            headers(("Retry-After" = u64, description = "Seconds until the next request is allowed"))),
+        (status = 503, description = "`UNAVAILABLE` or `SHUTTING_DOWN`", body = ErrorResponse),
+    )
+)]
+#[get("/puzzle")]
+async fn puzzle_handler(
+    req: HttpRequest,
+    state: web::Data<AppState>,
+    query: web::Query<PuzzleQuery>,
+) -> Result<HttpResponse, ApiError> {
+    let request_start = Instant::now();
+    let config = state.config.load();
+
+    if let Some(client) = req.peer_addr() {
+        if let Err(retry_after) = state
+            .rate_limiter
+            .check(client.ip(), config.rate_limit_per_minute)
+        {
+            let retry_after = retry_after.as_secs().max(1);
+            warn!(client = %client.ip(), "Puzzle rate limit exceeded");
+            return Err(ApiError::new(
+                ErrorCode::RateLimited,
+                "Too many requests, try again later",
+            )
+            .with_details(serde_json::json!({ "retry_after": retry_after }))
+            .with_retry_after(retry_after));
+        }
+    }
+
+    let key = challenge_key(
+        query.kind,
+        query.w,
+        query.h,
+        query.n,
+        query.pieces,
+        query.orientation,
+    );
+    let (width, height) = (key.width, key.height);
+    info!(%width, %height, "Incoming puzzle request");
+
+    let site = resolve_site(&req, &config, &query, width, height)?;
+    let difficulty = site
+        .as_ref()
+        .and_then(|site| site.difficulty)
+        .unwrap_or(config.difficulty);
+    let solution_ttl = site
+        .as_ref()
+        .and_then(|site| site.solution_ttl)
+        .unwrap_or(config.solution_ttl);
+
+    let accept = req
+        .headers()
+        .get(header::ACCEPT)
+        .and_then(|value| value.to_str().ok())
+        .filter(|_| config.negotiate_image_encoding);
+    let encoding = match accept {
+        Some(accept) => ImageEncoding::negotiate(accept, config.image_encoding),
+        None => config.image_encoding,
+    };
+
+    let key = key.with_encoding(encoding).with_difficulty(difficulty);
+
+    match state.generator.get_challenge(key).await {
+        Ok(images) => {
+            let now = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap()
+                .as_secs();
+
+            let id = uuid::Uuid::new_v4().to_string();
+            let expires_at = now + solution_ttl.as_secs();
+
+            let delivery = config.image_delivery;
+            let kept_images = (delivery == ImageDelivery::Url).then(|| images.clone());
+            state.generator.cache_solution(
+                id.clone(),
+                images.challenge.answer(),
+                expires_at,
+                kept_images,
+                site,
+            );
+
+            if config.log_puzzle_seeds {
+                info!(%id, key = ?key, seed = images.seed, "Puzzle seed");
+            }
+
+            let fields = ImageFields {
+                delivery,
+                id: &id,
+                base: api_base(&req),
+            };
+            let response = puzzle_response(&images, &fields);
+
+            info!(
+                %width,
+                %height,
+                elapsed_ms = request_start.elapsed().as_millis(),
+                cache_size = state.generator.cache_len(&key),
+                "Puzzle served"
+            );
+
+            Ok(HttpResponse::Ok().json(response))
+        }
+        Err(err) => {
+            warn!(%width, %height, error = %err, "No puzzle available");
+            Err(err.into())
+        }
+    }
+}
+
+/// Serves one image of a puzzle issued with URL delivery, until it is solved or expires.
+#[utoipa::path(
+    get,
+    path = "/v1/puzzle/{id}/{asset}",
+    params(
+        ("id" = String, Path, description = "Puzzle id"),
+        ("asset" = String, Path, description = "`background`, `piece`, `piece-{index}` or `hint`, with the image format's extension"),
+    ),
+    responses(
+        (status = 200, description = "Image bytes in the puzzle's format", content(
+            (Vec<u8> = "image/png"),
+            (Vec<u8> = "image/jpeg"),
+            (Vec<u8> = "image/webp"),
+        )),
+        (status = 404, description = "`NOT_FOUND`", body = ErrorResponse),
+    )
+)]
+#[get("/puzzle/{id}/{asset}")]
+async fn image_handler(
+    state: web::Data<AppState>,
+    path: web::Path<(String, String)>,
+) -> Result<HttpResponse, ApiError> {
+    let (id, asset) = path.into_inner();
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs();
+
+    let images = state
+        .generator
+        .get_solution(&id)
+        .filter(|entry| entry.expires_at > now)
+        .and_then(|entry| entry.images);
+
+    let asset = images.and_then(|images| {
+        let (name, extension) = asset.rsplit_once('.')?;
+        let encoding = images.encoding;
+        let piece = |index: usize| {
+            let bytes = images.pieces.get(index)?.clone();
+            Some((bytes, encoding.piece()))
+        };
+        let (bytes, format) = match (&images.challenge, name) {
+            (_, "background") => (images.background.clone(), encoding.background()),
+            (Challenge::Slider { .. }, "piece") | (Challenge::Click { .. }, "hint") => piece(0)?,
+            (Challenge::Slider { .. }, name) => name
+                .strip_prefix("piece-")
+                .and_then(|index| index.parse::<usize>().ok())
+                .and_then(piece)?,
+            _ => return None,
+        };
+        (format.extension() == extension).then_some((bytes, format))
+    });
+
+    let (bytes, format) =
+        asset.ok_or_else(|| ApiError::new(ErrorCode::NotFound, "Unknown puzzle image"))?;
+    Ok(HttpResponse::Ok()
+        .content_type(format.mime())
+        .insert_header(("Cache-Control", "no-store"))
+        .body(bytes))
+}
+
+/// Checks a submitted answer.
+#[utoipa::path(
+    post,
+    path = "/v1/puzzle/solution",
+    request_body = SolutionRequest,
+    responses(
+        (status = 200, body = VerifyResponse),
+        (status = 404, description = "`INVALID_ID`", body = ErrorResponse),
+        (status = 410, description = "`EXPIRED` or `TOO_MANY_ATTEMPTS`", body = ErrorResponse),
+        (status = 422, description = "`WRONG_ANSWER`", body = ErrorResponse),
+    )
+)]
+#[actix_web::post("/puzzle/solution")]
+async fn verify_handler(
+    state: web::Data<AppState>,
+    payload: web::Json<SolutionRequest>,
+) -> Result<HttpResponse, ApiError> {
+    let request_start = Instant::now();
+    let config = state.config.load();
+    let id = payload.id.clone();
+
+    // Ids issued for another site, or already solved and awaiting `/siteverify`, are
+    // treated as unknown.
+    let entry = state
+        .generator
+        .get_solution(&id)
+        .filter(|entry| {
+            let issuer = entry.site.as_ref().map(|site| site.site_key.as_str());
+            issuer == payload.sitekey.as_deref() && !entry.verified
+        })
+        .ok_or_else(|| {
+            warn!(%id, "Unknown solution id");
+            ApiError::new(ErrorCode::InvalidId, "Invalid request ID")
+        })?;
+
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs();
+
+    if entry.expires_at <= now {
+        warn!(%id, "Solution expired");
+        state.generator.remove_solution(&id);
+        return Err(ApiError::new(ErrorCode::Expired, "Captcha expired"));
+    }
+
+    let tolerance = entry
+        .site
+        .as_ref()
+        .and_then(|site| site.tolerance)
+        .unwrap_or(DEFAULT_TOLERANCE);
+    if solution_matches(&payload, &entry.answer, tolerance) {
+        info!(id = %id, elapsed_ms = request_start.elapsed().as_millis(), "Captcha solved");
+
+        if entry.site.is_some() {
+            // Kept until the site's backend redeems it through `/siteverify`.
+            state.generator.mark_verified(&id);
+        } else if config.immediate_cache_cleanup {
+            // 根据配置决定是否立即删除缓存
+            state.generator.remove_solution(&id);
+        }
+
+        return Ok(HttpResponse::Ok().json(VerifyResponse {
+            success: true,
+            message: "Verification successful".to_string(),
+        }));
+    }
+
+    // 验证失败，增加尝试次数
+    let attempts = state.generator.increment_attempts(&id).unwrap_or(0);
+    warn!(id = %id, submitted = ?payload.x, expected = ?entry.answer, attempts = %attempts, "Incorrect solution");
+
+    if attempts >= config.max_attempts {
+        // 达到最大失败次数后删除solution
+        state.generator.remove_solution(&id);
+        Err(ApiError::new(
+            ErrorCode::TooManyAttempts,
+            "Too many failed attempts, please request a new captcha",
+        )
+        .with_details(serde_json::json!({ "attempts": attempts })))
+    } else {
+        Err(
+            ApiError::new(ErrorCode::WrongAnswer, "Verification failed").with_details(
+                serde_json::json!({
+                    "attempts": attempts,
+                    "remaining": config.max_attempts - attempts,
+                }),
+            ),
+        )
+    }
+}
+
+/// Lets a site's backend confirm, once, that a puzzle it issued was solved.
+#[utoipa::path(
+    post,
+    path = "/v1/siteverify",
+    request_body = SiteVerifyRequest,
+    responses(
+        (status = 200, body = SiteVerifyResponse),
+        (status = 400, description = "`NOT_VERIFIED`", body = ErrorResponse),
+        (status = 401, description = "`INVALID_SECRET`", body = ErrorResponse),
+        (status = 404, description = "`INVALID_ID`", body = ErrorResponse),
+        (status = 410, description = "`EXPIRED`", body = ErrorResponse),
+    )
+)]
+#[actix_web::post("/siteverify")]
+async fn siteverify_handler(
+    state: web::Data<AppState>,
+    payload: web::Json<SiteVerifyRequest>,
+) -> Result<HttpResponse, ApiError> {
+    let config = state.config.load();
+    let Some(site) = config.sites.by_secret(&payload.secret) else {
+        warn!("Unknown secret key");
+        return Err(ApiError::new(
+            ErrorCode::InvalidSecret,
+            "Invalid secret key",
+        ));
+    };
+
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs();
+    let entry = state.generator.get_solution(&payload.id).filter(|entry| {
+        entry
+            .site
+            .as_ref()
+            .is_some_and(|issuer| issuer.site_key == site.site_key)
+    });
+
+    let rejection = match entry {
+        Some(entry) if entry.verified && entry.expires_at > now => {
+            state.generator.remove_solution(&payload.id);
+            info!(id = %payload.id, site = %site.site_key, "Site verification succeeded");
+            return Ok(HttpResponse::Ok().json(SiteVerifyResponse {
+                success: true,
+                sitekey: site.site_key.clone(),
+            }));
+        }
+        // Redeemed ids are removed, so they report as unknown.
+        None => ApiError::new(ErrorCode::InvalidId, "Unknown or already verified id"),
+        Some(entry) if entry.expires_at <= now => {
+            ApiError::new(ErrorCode::Expired, "Captcha expired")
+        }
+        Some(_) => ApiError::new(ErrorCode::NotVerified, "Captcha not solved"),
+    };
+    warn!(id = %payload.id, site = %site.site_key, code = %rejection.code, "Site verification failed");
+    Err(rejection)
+}
+
+#[get("/health")]
+async fn health_handler(state: web::Data<AppState>) -> impl Responder {
+    let config = state.config.load();
+    HttpResponse::Ok().json(serde_json::json!({
+        "status": "ok",
+        "prefill_sizes": config.prefill_dimensions,
+    }))
+}
+
+/// What both health probes report; each decides its own status from the flags.
+struct HealthChecks {
+    dispatcher_running: bool,
+    shutting_down: bool,
+    prefill_reached: bool,
+    details: serde_json::Value,
+}
+
+fn health_checks(state: &AppState) -> HealthChecks {
+    let config = state.config.load();
+    let generator = &state.generator;
+    let dispatcher_running = generator.dispatcher_running();
+    let shutting_down = generator.is_shutting_down();
+    let prefill_reached = generator.prefill_reached(&config);
+    let sizes: Vec<_> = generator
+        .prefill_levels(&config)
+        .into_iter()
+        .map(|(key, cached)| {
+            serde_json::json!({
+                "size": format!("{}x{}", key.width, key.height),
+                "cached": cached,
+                "target": config.cache_prefill_per_size,
+            })
+        })
+        .collect();
+
+    HealthChecks {
+        dispatcher_running,
+        shutting_down,
+        prefill_reached,
+        details: serde_json::json!({
+            "dispatcher": if dispatcher_running { "running" } else { "stopped" },
+            "shutting_down": shutting_down,
+            "prefill": {
+                "reached": prefill_reached,
+                "min": config.ready_min_prefill,
+                "sizes": sizes,
+            },
+            "queue": {
+                "depth": generator.queue_depth(),
+                "capacity": generator.queue_capacity(),
+            },
+            // Solutions live in process memory, so the store is reachable whenever we are.
+            "solutions": {
+                "store": "memory",
+                "connected": true,
+                "count": generator.solution_count(),
+            },
+        }),
+    }
+}
+
+fn health_response(healthy: bool, checks: HealthChecks) -> HttpResponse {
+    let mut response = if healthy {
+        HttpResponse::Ok()
+    } else {
+        HttpResponse::ServiceUnavailable()
+    };
+    response
+        .insert_header((header::CACHE_CONTROL, "no-store"))
+        .json(serde_json::json!({
+            "status": if healthy { "ok" } else { "unavailable" },
+            "checks": checks.details,
+        }))
+}
+
+/// Fails only when the dispatcher died on its own; restarting the process is the fix.
+#[get("/health/live")]
+async fn liveness_handler(state: web::Data<AppState>) -> impl Responder {
+    let checks = health_checks(&state);
+    health_response(checks.dispatcher_running || checks.shutting_down, checks)
+}
+
+/// Fails until every prefill size first holds `ready_min_prefill` puzzles, and whenever
+/// the dispatcher is stopped or the server is shutting down.
+#[get("/health/ready")]
+async fn readiness_handler(state: web::Data<AppState>) -> impl Responder {
+    let checks = health_checks(&state);
+    let ready = checks.dispatcher_running && !checks.shutting_down && checks.prefill_reached;
+    health_response(ready, checks)
+}
+
+/// Settings that only take effect on restart.
+fn restart_only_changes(previous: &AppConfig, next: &AppConfig) -> Vec<&'static str> {
+    [
+        ("host", previous.host != next.host),
+        ("port", previous.port != next.port),
+        ("workers", previous.workers != next.workers),
+        ("log_level", previous.log_level != next.log_level),
+    ]
+    .into_iter()
+    .filter_map(|(key, changed)| changed.then_some(key))
+    .collect()
+}
+
+/// Re-reads the configuration, swaps it into `state` and re-plans the generator.
+/// Cached puzzles and outstanding solutions survive; on error nothing changes.
+pub fn reload_config(state: &AppState) -> Result<Vec<&'static str>, ConfigError> {
+    let previous = state.config.load();
+    let next = state.config.reload()?;
+    state.generator.reconfigure(&next);
+    state.generator.fill_cache(&next);
+
+    let restart_required = restart_only_changes(&previous, &next);
+    if restart_required.is_empty() {
+        info!("Configuration reloaded");
+    } else {
+        warn!(
+            ?restart_required,
+            "Configuration reloaded; some changes need a restart"
+        );
+    }
+    Ok(restart_required)
+}
+
+/// The public API, mounted under [`API_V1`] and at the root.
+fn api_routes(cfg: &mut web::ServiceConfig) {
+    cfg.service(puzzle_handler)
+        .service(image_handler)
+        .service(verify_handler)
+        .service(siteverify_handler);
+}
+
+/// Answers requests that match no route.
+async fn not_found_handler(req: HttpRequest) -> Result<HttpResponse, ApiError> {
+    Err(ApiError::new(
+        ErrorCode::NotFound,
+        format!("No endpoint at {} {}", req.method(), req.path()),
+    ))
+}
+
+/// Registers every route; shared by [`run_server`] and in-process tests.
+pub fn configure(cfg: &mut web::ServiceConfig) {
+    // Malformed bodies, queries and paths, and unknown routes, get the same envelope as
+    // every other error.
+    cfg.app_data(
+        web::JsonConfig::default()
+            .error_handler(|err, _| ApiError::invalid_request(err.to_string()).into()),
+    )
+    .app_data(
+        web::QueryConfig::default()
+            .error_handler(|err, _| ApiError::invalid_request(err.to_string()).into()),
+    )
+    .app_data(
+        web::PathConfig::default()
+            .error_handler(|err, _| ApiError::invalid_request(err.to_string()).into()),
+    )
+    .default_service(web::to(not_found_handler))
+    .service(
+        web::scope(API_V1)
+            .configure(api_routes)
+            .service(openapi::openapi_handler),
+    )
+    // Unversioned aliases from before `/v1`.
+    .configure(api_routes)
+    .service(health_handler)
+    .service(liveness_handler)
+    .service(readiness_handler)
+    .service(admin::scope());
+}
+
+/// Reloads the configuration whenever the process receives SIGHUP.
+#[cfg(unix)]
+fn spawn_sighup_reload(state: AppState) {
+    use tokio::signal::unix::{signal, SignalKind};
+
+    spawn(async move {
+        let mut hangups = match signal(SignalKind::hangup()) {
+            Ok(hangups) => hangups,
+            Err(err) => {
+                tracing::error!(error = ?err, "Failed to install SIGHUP handler");
+                return;
+            }
+        };
+        while hangups.recv().await.is_some() {
+            let state = state.clone();
+            match spawn_blocking(move || reload_config(&state)).await {
+                Ok(Ok(_)) => {}
+                Ok(Err(err)) => warn!(error = %err, "Configuration reload rejected"),
+                Err(err) => tracing::error!(error = ?err, "Configuration reload panicked"),
+            }
+        }
+    });
+}
+
+/// Resolves on Ctrl-C, or on SIGTERM on unix.
+async fn shutdown_signal() {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{signal, SignalKind};
+
+        match signal(SignalKind::terminate()) {
+            Ok(mut terminate) => {
+                tokio::select! {
+                    _ = tokio::signal::ctrl_c() => {}
+                    _ = terminate.recv() => {}
+                }
+                return;
+            }
+            Err(err) => tracing::error!(error = ?err, "Failed to install SIGTERM handler"),
+        }
+    }
+    if let Err(err) = tokio::signal::ctrl_c().await {
+        tracing::error!(error = ?err, "Failed to listen for Ctrl-C");
+    }
+}
+
+/// Serves until Ctrl-C or SIGTERM, then shuts down in order: `/puzzle` starts answering
+/// 503 and queued generations are cancelled, in-flight requests (verifications included)
+/// are drained, and outstanding solutions and cached puzzles are saved to `snapshot_file`
+/// if one is set. The cache is also saved every `snapshot_interval`, and a snapshot found
+/// at startup warms the cache before prefilling.
+pub async fn run_server(shared: SharedConfig) -> std::io::Result<()> {
+    let config = shared.load();
+    let listener = TcpListener::bind((config.host.as_str(), config.port))?;
+    serve(shared, listener, shutdown_signal(), true).await
+}
+
+/// [`run_server`] on an already bound listener, such as one on an ephemeral port, that
+/// shuts down once `shutdown` resolves. No signal handlers are installed, so SIGHUP does
+/// not reload; `host` and `port` from the configuration are ignored.
+pub async fn run_server_on(
+    shared: SharedConfig,
+    listener: TcpListener,
+    shutdown: impl Future<Output = ()> + 'static,
+) -> std::io::Result<()> {
+    serve(shared, listener, shutdown, false).await
+}
+
+async fn serve(
+    shared: SharedConfig,
+    listener: TcpListener,
+    shutdown: impl Future<Output = ()> + 'static,
+    reload_on_hangup: bool,
+) -> std::io::Result<()> {
+    let config = shared.load();
+    let generator = Arc::new(PuzzleGenerator::new(config.clone()));
+    if let Some(path) = &config.snapshot_file {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs();
+        match generator.restore_snapshot(path, &config.sites, now) {
+            Ok((solutions, puzzles)) => info!(
+                solutions,
+                puzzles,
+                path = %path.display(),
+                "Restored from snapshot"
+            ),
+            Err(err) => warn!(error = %err, path = %path.display(), "Failed to restore snapshot"),
+        }
+    }
+    generator.fill_cache(&config);
+
+    let rate_limiter = RateLimiter::new();
+    let state = AppState {
+        generator: generator.clone(),
+        config: shared.clone(),
+        rate_limiter: rate_limiter.clone(),
+    };
+
+    #[cfg(unix)]
+    if reload_on_hangup {
+        spawn_sighup_reload(state.clone());
+    }
+    #[cfg(not(unix))]
+    let _ = reload_on_hangup;
+
+    let cleanup_generator = generator.clone();
+
+    let cleanup_config = shared.clone();
+    let cleanup = spawn(async move {
+        loop {
+            // Re-read each round so a reload can change the interval.
+            time::sleep(cleanup_config.load().cleanup_interval).await;
+            let cleanup_generator = cleanup_generator.clone();
+            let rate_limiter = rate_limiter.clone();
+            if let Err(err) = spawn_blocking(move || {
+                let (removed, remaining) = cleanup_generator.cleanup();
+                let now = SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .unwrap()
+                    .as_secs();
+                let expired_solutions = cleanup_generator.cleanup_solutions(now);
+                let idle_clients = rate_limiter.prune();
+                tracing::info!(
+                    removed,
+                    remaining,
+                    expired_solutions,
+                    idle_clients,
+                    "Cache cleanup completed"
+                );
+            })
+            .await
+            {
+                tracing::error!(error=?err, "Cleanup task panic");
+            }
+        }
+    });
+
+    let snapshot_config = shared.clone();
+    let snapshot_generator = generator.clone();
+    let snapshots = spawn(async move {
+        loop {
+            let Some(interval) = snapshot_config.load().snapshot_interval else {
+                // Check again later in case a reload enables snapshots.
+                time::sleep(Duration::from_secs(60)).await;
+                continue;
+            };
+            time::sleep(interval).await;
+            let Some(path) = snapshot_config.load().snapshot_file.clone() else {
+                continue;
+            };
+            let generator = snapshot_generator.clone();
+            let now = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap()
+                .as_secs();
+            match spawn_blocking(move || generator.save_cache_snapshot(&path, now)).await {
+                Ok(Ok(puzzles)) => info!(puzzles, "Saved cache snapshot"),
+                Ok(Err(err)) => warn!(error = %err, "Failed to save cache snapshot"),
+                Err(err) => tracing::error!(error = ?err, "Snapshot task panic"),
+            }
+        }
+    });
+
+    let server = HttpServer::new(move || {
+        App::new()
+            .app_data(web::Data::new(state.clone()))
+            .configure(configure)
+    })
+    .listen(listener)?
+    .workers(config.workers)
+    .disable_signals()
+    .run();
+
+    let handle = server.handle();
+    let shutdown_generator = generator.clone();
+    spawn(async move {
+        shutdown.await;
+        info!("Shutting down: refusing new puzzles and draining in-flight requests");
+        shutdown_generator.shutdown();
+        handle.stop(true).await;
+    });
+
+    server.await?;
+    cleanup.abort();
+    snapshots.abort();
+
+    if let Some(path) = &shared.load().snapshot_file {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs();
+        match generator.save_snapshot(path, now) {
+            Ok((solutions, puzzles)) => info!(
+                solutions,
+                puzzles,
+                path = %path.display(),
+                "Saved snapshot"
+            ),
+            Err(err) => {
+                tracing::error!(error = %err, path = %path.display(), "Failed to save snapshot")
+            }
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,38 @@
+//! The OpenAPI 3 document for the `/v1` API, generated from the types in [`crate::api`].
+
+use actix_web::{get, HttpResponse};
+use utoipa::OpenApi;
+
+use crate::api::{
+    ClickPoint, ErrorCode, ErrorResponse, PieceImage, PuzzleResponse, SiteVerifyRequest,
+    SiteVerifyResponse, SliderSubmission, SolutionRequest, VerifyResponse,
+};
+
+#[derive(OpenApi)]
+#[openapi(
+    info(title = "Slider Captcha API", version = "1"),
+    paths(
+        super::puzzle_handler,
+        super::image_handler,
+        super::verify_handler,
+        super::siteverify_handler
+    ),
+    components(schemas(
+        PuzzleResponse,
+        PieceImage,
+        SolutionRequest,
+        SliderSubmission,
+        ClickPoint,
+        VerifyResponse,
+        SiteVerifyRequest,
+        SiteVerifyResponse,
+        ErrorResponse,
+        ErrorCode
+    ))
+)]
+pub struct ApiDoc;
+
+#[get("/openapi.json")]
+pub(super) async fn openapi_handler() -> HttpResponse {
+    HttpResponse::Ok().json(ApiDoc::openapi())
+}
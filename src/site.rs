@@ -0,0 +1,251 @@
+//! Per-site settings for serving several products from one deployment.
+//!
+//! Each site has a public site key, sent by browsers as `/puzzle?sitekey=...`, and a
+//! private secret key its backend uses with `/siteverify`. Solutions remember the site
+//! that issued them, so one site's puzzle ids cannot be verified by another.
+
+use std::{
+    collections::HashMap,
+    fmt, fs,
+    hash::{BuildHasher, RandomState},
+    path::Path,
+    sync::Arc,
+    time::Duration,
+};
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::render::Difficulty;
+
+pub use crate::api::ChallengeType;
+
+/// Margin `/puzzle/solution` accepts for slider answers, relative to the moving axis.
+pub const DEFAULT_TOLERANCE: f64 = 0.015;
+
+/// Settings for one site. Unset fields fall back to the server-wide configuration.
+///
+/// Serializing redacts the secret key, for printing the effective configuration.
+#[derive(Clone, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct SiteConfig {
+    pub site_key: String,
+    #[serde(serialize_with = "redact")]
+    pub secret_key: String,
+    /// `WxH` sizes this site may request; empty allows any valid size.
+    #[serde(
+        default,
+        deserialize_with = "deserialize_sizes",
+        serialize_with = "serialize_sizes",
+        skip_serializing_if = "Vec::is_empty"
+    )]
+    pub allowed_sizes: Vec<(u32, u32)>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub difficulty: Option<Difficulty>,
+    #[serde(
+        default,
+        rename = "solution_ttl_secs",
+        deserialize_with = "deserialize_secs",
+        serialize_with = "serialize_secs",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub solution_ttl: Option<Duration>,
+    /// Slider answer margin, relative to the moving axis.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tolerance: Option<f64>,
+    /// Exact `Origin` header values allowed to request puzzles; empty allows any.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub allowed_origins: Vec<String>,
+    /// Empty allows every challenge type.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub challenge_types: Vec<ChallengeType>,
+}
+
+impl SiteConfig {
+    pub fn allows_size(&self, width: u32, height: u32) -> bool {
+        self.allowed_sizes.is_empty() || self.allowed_sizes.contains(&(width, height))
+    }
+
+    pub fn allows_origin(&self, origin: Option<&str>) -> bool {
+        self.allowed_origins.is_empty()
+            || origin
+                .is_some_and(|origin| self.allowed_origins.iter().any(|allowed| allowed == origin))
+    }
+
+    pub fn allows_type(&self, kind: ChallengeType) -> bool {
+        self.challenge_types.is_empty() || self.challenge_types.contains(&kind)
+    }
+}
+
+// Written by hand so the secret key never reaches the logs.
+impl fmt::Debug for SiteConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SiteConfig")
+            .field("site_key", &self.site_key)
+            .field("allowed_sizes", &self.allowed_sizes)
+            .field("difficulty", &self.difficulty)
+            .field("solution_ttl", &self.solution_ttl)
+            .field("tolerance", &self.tolerance)
+            .field("allowed_origins", &self.allowed_origins)
+            .field("challenge_types", &self.challenge_types)
+            .finish_non_exhaustive()
+    }
+}
+
+fn deserialize_sizes<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Vec<(u32, u32)>, D::Error> {
+    Vec::<String>::deserialize(deserializer)?
+        .iter()
+        .map(|raw| {
+            raw.split_once('x')
+                .and_then(|(width, height)| {
+                    Some((width.trim().parse().ok()?, height.trim().parse().ok()?))
+                })
+                .ok_or_else(|| {
+                    serde::de::Error::custom(format!("invalid size {raw}, expected WxH"))
+                })
+        })
+        .collect()
+}
+
+fn serialize_sizes<S: Serializer>(sizes: &[(u32, u32)], serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.collect_seq(
+        sizes
+            .iter()
+            .map(|(width, height)| format!("{width}x{height}")),
+    )
+}
+
+fn serialize_secs<S: Serializer>(ttl: &Option<Duration>, serializer: S) -> Result<S::Ok, S::Error> {
+    match ttl {
+        Some(ttl) => serializer.serialize_u64(ttl.as_secs()),
+        None => serializer.serialize_none(),
+    }
+}
+
+fn redact<S: Serializer>(_: &str, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str("<redacted>")
+}
+
+fn deserialize_secs<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<Duration>, D::Error> {
+    Ok(Option::<u64>::deserialize(deserializer)?.map(Duration::from_secs))
+}
+
+/// Why a sites file could not be loaded.
+#[derive(Debug)]
+pub struct SiteError(String);
+
+impl fmt::Display for SiteError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for SiteError {}
+
+/// All configured sites, looked up by site key.
+#[derive(Clone, Debug, Default)]
+pub struct SiteRegistry {
+    sites: HashMap<String, Arc<SiteConfig>>,
+    /// Site keys by a keyed hash of their secret, so `/siteverify` does not scan and
+    /// compare every secret.
+    by_secret: HashMap<u64, String>,
+    secret_hasher: RandomState,
+}
+
+impl SiteRegistry {
+    /// Rejects duplicate site or secret keys, empty keys and out-of-range values.
+    pub fn new(sites: Vec<SiteConfig>) -> Result<Self, SiteError> {
+        let mut registry = HashMap::with_capacity(sites.len());
+        let mut by_secret = HashMap::with_capacity(sites.len());
+        let secret_hasher = RandomState::new();
+        for site in sites {
+            if site.site_key.is_empty() || site.secret_key.is_empty() {
+                return Err(SiteError(
+                    "site_key and secret_key must not be empty".into(),
+                ));
+            }
+            if site
+                .tolerance
+                .is_some_and(|tolerance| !(tolerance > 0.0 && tolerance < 1.0))
+            {
+                return Err(SiteError(format!(
+                    "site {} tolerance must be between 0 and 1",
+                    site.site_key
+                )));
+            }
+            if site.solution_ttl.is_some_and(|ttl| ttl.is_zero()) {
+                return Err(SiteError(format!(
+                    "site {} solution_ttl_secs must be positive",
+                    site.site_key
+                )));
+            }
+            let key = site.site_key.clone();
+            let secret_hash = secret_hasher.hash_one(&site.secret_key);
+            if by_secret.insert(secret_hash, key.clone()).is_some() {
+                return Err(SiteError(format!(
+                    "site {key} reuses another site's secret_key"
+                )));
+            }
+            if registry.insert(key.clone(), Arc::new(site)).is_some() {
+                return Err(SiteError(format!("duplicate site_key {key}")));
+            }
+        }
+        Ok(Self {
+            sites: registry,
+            by_secret,
+            secret_hasher,
+        })
+    }
+
+    /// Reads a JSON array of [`SiteConfig`]s.
+    pub fn from_json_file(path: impl AsRef<Path>) -> Result<Self, SiteError> {
+        let path = path.as_ref();
+        let raw = fs::read_to_string(path)
+            .map_err(|err| SiteError(format!("{}: {err}", path.display())))?;
+        let sites = serde_json::from_str(&raw)
+            .map_err(|err| SiteError(format!("{}: {err}", path.display())))?;
+        Self::new(sites)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.sites.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.sites.len()
+    }
+
+    /// Sites ordered by site key.
+    pub fn sites(&self) -> Vec<&Arc<SiteConfig>> {
+        let mut sites: Vec<_> = self.sites.values().collect();
+        sites.sort_by(|a, b| a.site_key.cmp(&b.site_key));
+        sites
+    }
+
+    pub fn get(&self, site_key: &str) -> Option<&Arc<SiteConfig>> {
+        self.sites.get(site_key)
+    }
+
+    /// The site owning `secret_key`; the stored secret is compared in constant time.
+    pub fn by_secret(&self, secret_key: &str) -> Option<&Arc<SiteConfig>> {
+        let site_key = self
+            .by_secret
+            .get(&self.secret_hasher.hash_one(secret_key))?;
+        self.sites
+            .get(site_key)
+            .filter(|site| secrets_match(&site.secret_key, secret_key))
+    }
+}
+
+/// Compares every byte so the response time does not reveal a matching prefix.
+pub(crate) fn secrets_match(expected: &str, presented: &str) -> bool {
+    expected.len() == presented.len()
+        && expected
+            .bytes()
+            .zip(presented.bytes())
+            .fold(0, |diff, (a, b)| diff | (a ^ b))
+            == 0
+}
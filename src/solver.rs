@@ -0,0 +1,326 @@
+//! Reference attacks against slider puzzles, used to measure how easy they are for bots.
+//!
+//! Each [`Attack`] sees only what a client receives: the background, the piece images
+//! and each piece's fixed coordinate. A guess counts as solved when every piece lands
+//! within the same margin the server verifies against.
+
+use image::{imageops, GenericImageView, RgbaImage};
+use rand::{rngs::StdRng, SeedableRng};
+
+use crate::{
+    background::WeightedBackgrounds,
+    error::PuzzleError,
+    puzzle::{verify_pieces, Orientation, SliderPuzzle},
+    render::{Difficulty, PieceStyle},
+};
+
+/// Tolerance used by `/puzzle/solution` for slider answers.
+pub const SOLVE_MARGIN: f64 = 0.015;
+
+/// Inner border of the piece ignored by template matching, where outline and glow sit.
+const TEMPLATE_INSET: u32 = 8;
+
+/// What a client sees of a slider puzzle.
+pub struct SolverInput {
+    pub background: RgbaImage,
+    /// Piece images with any shadow padding removed, in slide order.
+    pub pieces: Vec<RgbaImage>,
+    /// Each piece's revealed coordinate on the fixed axis, in pixels.
+    pub fixed: Vec<u32>,
+    pub orientation: Orientation,
+}
+
+impl SolverInput {
+    pub fn from_puzzle(puzzle: &SliderPuzzle) -> Self {
+        let background = puzzle.cropped_puzzle.to_rgba8();
+        let (width, height) = background.dimensions();
+        let padding = puzzle.piece_padding;
+
+        let pieces = puzzle
+            .pieces
+            .iter()
+            .map(|piece| {
+                let (piece_width, piece_height) = piece.image.dimensions();
+                piece
+                    .image
+                    .crop_imm(
+                        padding,
+                        padding,
+                        piece_width - 2 * padding,
+                        piece_height - 2 * padding,
+                    )
+                    .to_rgba8()
+            })
+            .collect();
+        let fixed = puzzle
+            .pieces
+            .iter()
+            .map(|piece| match puzzle.orientation {
+                Orientation::Horizontal => (piece.y * height as f64).round() as u32,
+                Orientation::Vertical => (piece.x * width as f64).round() as u32,
+            })
+            .collect();
+
+        Self {
+            background,
+            pieces,
+            fixed,
+            orientation: puzzle.orientation,
+        }
+    }
+
+    /// Length of the moving axis, in pixels.
+    fn span(&self) -> u32 {
+        match self.orientation {
+            Orientation::Horizontal => self.background.width(),
+            Orientation::Vertical => self.background.height(),
+        }
+    }
+
+    /// Top-left pixel of piece `index` placed at `position` along the moving axis.
+    fn place(&self, index: usize, position: u32) -> (u32, u32) {
+        match self.orientation {
+            Orientation::Horizontal => (position, self.fixed[index]),
+            Orientation::Vertical => (self.fixed[index], position),
+        }
+    }
+
+    /// Moving-axis positions where piece `index` fits inside the background.
+    fn candidates(&self, index: usize) -> std::ops::RangeInclusive<u32> {
+        let (piece_width, piece_height) = self.pieces[index].dimensions();
+        let length = match self.orientation {
+            Orientation::Horizontal => piece_width,
+            Orientation::Vertical => piece_height,
+        };
+        0..=self.span().saturating_sub(length)
+    }
+
+    /// Picks the best-scoring candidate for every piece, as relative positions. A piece
+    /// with no candidate gets `NaN`, which never verifies.
+    fn best_positions(&self, score: impl Fn(usize, u32) -> Option<f64>) -> Vec<f64> {
+        (0..self.pieces.len())
+            .map(|index| {
+                self.candidates(index)
+                    .filter_map(|position| Some((position, score(index, position)?)))
+                    .max_by(|a, b| a.1.total_cmp(&b.1))
+                    .map_or(f64::NAN, |(position, _)| {
+                        position as f64 / self.span() as f64
+                    })
+            })
+            .collect()
+    }
+}
+
+/// One automated strategy for locating the pieces.
+pub trait Attack: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    /// Guessed target of every piece along the moving axis, relative to the image size.
+    fn solve(&self, input: &SolverInput) -> Vec<f64>;
+}
+
+fn luma(pixel: &image::Rgba<u8>) -> f64 {
+    0.299 * pixel[0] as f64 + 0.587 * pixel[1] as f64 + 0.114 * pixel[2] as f64
+}
+
+/// Looks for pixels whose alpha differs from the rest of the background.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct AlphaScan;
+
+impl Attack for AlphaScan {
+    fn name(&self) -> &'static str {
+        "alpha-scan"
+    }
+
+    fn solve(&self, input: &SolverInput) -> Vec<f64> {
+        input.best_positions(|index, position| {
+            let (x, y) = input.place(index, position);
+            let alpha = input.background.get_pixel(x, y)[3];
+            (alpha < 255).then_some(-(position as f64))
+        })
+    }
+}
+
+/// Scores each position by the brightness step across the hole's leading and
+/// trailing edges, where the background darkens into the hole and lightens out of it.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct EdgeDetection;
+
+impl Attack for EdgeDetection {
+    fn name(&self) -> &'static str {
+        "edge-detection"
+    }
+
+    fn solve(&self, input: &SolverInput) -> Vec<f64> {
+        let background = &input.background;
+        let (width, height) = background.dimensions();
+        let at = |x: u32, y: u32| luma(background.get_pixel(x.min(width - 1), y.min(height - 1)));
+
+        input.best_positions(|index, position| {
+            let (piece_width, piece_height) = input.pieces[index].dimensions();
+            let (x0, y0) = input.place(index, position);
+            let step = match input.orientation {
+                Orientation::Horizontal => (0..piece_height)
+                    .map(|dy| {
+                        let y = y0 + dy;
+                        let entering = at(x0.saturating_sub(1), y) - at(x0, y);
+                        let end = x0 + piece_width;
+                        let leaving = at(end, y) - at(end - 1, y);
+                        entering + leaving
+                    })
+                    .sum::<f64>(),
+                Orientation::Vertical => (0..piece_width)
+                    .map(|dx| {
+                        let x = x0 + dx;
+                        let entering = at(x, y0.saturating_sub(1)) - at(x, y0);
+                        let end = y0 + piece_height;
+                        let leaving = at(x, end) - at(x, end - 1);
+                        entering + leaving
+                    })
+                    .sum::<f64>(),
+            };
+            Some(step)
+        })
+    }
+}
+
+/// Zero-mean normalised cross-correlation of the piece's interior against the
+/// background, which survives the hole being uniformly darkened.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct TemplateMatch;
+
+impl Attack for TemplateMatch {
+    fn name(&self) -> &'static str {
+        "template-match"
+    }
+
+    fn solve(&self, input: &SolverInput) -> Vec<f64> {
+        let templates: Vec<(Vec<f64>, u32, u32)> = input
+            .pieces
+            .iter()
+            .map(|piece| {
+                let (width, height) = piece.dimensions();
+                let inset = TEMPLATE_INSET.min(width / 4).min(height / 4);
+                let inner =
+                    imageops::crop_imm(piece, inset, inset, width - 2 * inset, height - 2 * inset);
+                let values = inner.pixels().map(|(_, _, pixel)| luma(&pixel)).collect();
+                (values, inset, width - 2 * inset)
+            })
+            .collect();
+
+        input.best_positions(|index, position| {
+            let (template, inset, stride) = &templates[index];
+            let (x0, y0) = input.place(index, position);
+            let window: Vec<f64> = template
+                .iter()
+                .enumerate()
+                .map(|(i, _)| {
+                    let (dx, dy) = (i as u32 % stride, i as u32 / stride);
+                    luma(input.background.get_pixel(x0 + inset + dx, y0 + inset + dy))
+                })
+                .collect();
+            Some(correlation(template, &window))
+        })
+    }
+}
+
+fn correlation(a: &[f64], b: &[f64]) -> f64 {
+    let n = a.len() as f64;
+    let (mean_a, mean_b) = (a.iter().sum::<f64>() / n, b.iter().sum::<f64>() / n);
+    let (mut cross, mut var_a, mut var_b) = (0.0, 0.0, 0.0);
+    for (x, y) in a.iter().zip(b) {
+        let (da, db) = (x - mean_a, y - mean_b);
+        cross += da * db;
+        var_a += da * da;
+        var_b += db * db;
+    }
+    if var_a == 0.0 || var_b == 0.0 {
+        return 0.0;
+    }
+    cross / (var_a * var_b).sqrt()
+}
+
+/// The built-in attacks, strongest last.
+pub fn standard_attacks() -> Vec<Box<dyn Attack>> {
+    vec![
+        Box::new(AlphaScan),
+        Box::new(EdgeDetection),
+        Box::new(TemplateMatch),
+    ]
+}
+
+/// The generator settings a batch of puzzles is rendered with.
+#[derive(Clone, Debug)]
+pub struct SolverConfig {
+    pub width: u32,
+    pub height: u32,
+    pub pieces: usize,
+    pub orientation: Orientation,
+    pub backgrounds: WeightedBackgrounds,
+    pub piece_style: PieceStyle,
+    pub difficulty: Difficulty,
+}
+
+impl SolverConfig {
+    /// Renders the puzzle for `seed`, the same way the server would for this configuration.
+    pub fn generate(&self, seed: u64) -> Result<SliderPuzzle, PuzzleError> {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let background = self.backgrounds.generate(self.width, self.height, &mut rng);
+        SliderPuzzle::from_background_styled(
+            background,
+            self.pieces,
+            self.orientation,
+            &self.piece_style,
+            &self.difficulty.perturbation(),
+            &mut rng,
+        )
+    }
+}
+
+/// How often one attack solved a batch of puzzles.
+#[derive(Clone, Debug, PartialEq)]
+pub struct AttackReport {
+    pub attack: &'static str,
+    pub solved: usize,
+    pub total: usize,
+}
+
+impl AttackReport {
+    pub fn rate(&self) -> f64 {
+        if self.total == 0 {
+            0.0
+        } else {
+            self.solved as f64 / self.total as f64
+        }
+    }
+}
+
+/// Runs every attack against the puzzles rendered for `seeds`.
+pub fn evaluate(
+    config: &SolverConfig,
+    seeds: std::ops::Range<u64>,
+    attacks: &[Box<dyn Attack>],
+) -> Result<Vec<AttackReport>, PuzzleError> {
+    let mut reports: Vec<AttackReport> = attacks
+        .iter()
+        .map(|attack| AttackReport {
+            attack: attack.name(),
+            solved: 0,
+            total: 0,
+        })
+        .collect();
+
+    for seed in seeds {
+        let puzzle = config.generate(seed)?;
+        let input = SolverInput::from_puzzle(&puzzle);
+        let targets = puzzle.targets();
+        for (attack, report) in attacks.iter().zip(&mut reports) {
+            report.total += 1;
+            if verify_pieces(&targets, &attack.solve(&input), SOLVE_MARGIN) {
+                report.solved += 1;
+            }
+        }
+    }
+
+    Ok(reports)
+}
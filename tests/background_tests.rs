@@ -0,0 +1,63 @@
+use std::sync::Arc;
+
+use image::RgbaImage;
+use rand::{rngs::StdRng, RngCore, SeedableRng};
+use slider_captcha_server::background::{
+    parse_background_styles, BackgroundGenerator, BackgroundStyle, WeightedBackgrounds,
+};
+
+#[test]
+fn every_style_renders_opaque_reproducible_backgrounds() {
+    for style in BackgroundStyle::ALL {
+        let generator = style.generator();
+        let render = |seed| generator.generate(320, 180, &mut StdRng::seed_from_u64(seed));
+
+        let image = render(5);
+        assert_eq!(image.dimensions(), (320, 180), "{}", style.name());
+        assert!(
+            image.pixels().all(|pixel| pixel[3] == 255),
+            "{}",
+            style.name()
+        );
+        assert_eq!(image, render(5), "{} should be seeded", style.name());
+        assert_ne!(image, render(6), "{} should vary by seed", style.name());
+    }
+
+    // Tiny canvases must not panic either.
+    for style in BackgroundStyle::ALL {
+        style
+            .generator()
+            .generate(20, 20, &mut StdRng::seed_from_u64(1));
+    }
+}
+
+struct Flat;
+
+impl BackgroundGenerator for Flat {
+    fn name(&self) -> &'static str {
+        "flat"
+    }
+
+    fn generate(&self, width: u32, height: u32, _rng: &mut dyn RngCore) -> RgbaImage {
+        RgbaImage::from_pixel(width, height, image::Rgba([1, 2, 3, 255]))
+    }
+}
+
+#[test]
+fn weighted_backgrounds_accept_custom_generators() {
+    let backgrounds =
+        WeightedBackgrounds::from_styles(&[(BackgroundStyle::Noise, 0)]).with(Arc::new(Flat), 3);
+    let mut rng = StdRng::seed_from_u64(9);
+
+    for _ in 0..20 {
+        assert_eq!(backgrounds.pick(&mut rng).name(), "flat");
+    }
+
+    let empty = WeightedBackgrounds::new();
+    assert_eq!(empty.pick(&mut rng).name(), "gradient");
+
+    assert_eq!(
+        parse_background_styles("noise:2, Voronoi ,bogus,stripes:0,polygons:x"),
+        vec![(BackgroundStyle::Noise, 2), (BackgroundStyle::Voronoi, 1)]
+    );
+}
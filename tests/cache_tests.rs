@@ -1,4 +1,4 @@
-use std::time::Duration;
+use std::{sync::Arc, time::Duration};
 
 use slider_captcha_server::cache::ExpiringCache;
 
@@ -28,3 +28,41 @@ async fn expiring_cache_truncates_to_max_len() {
     assert!(*first == 3 || *second == 3, "Newest entry should be retained");
 }
 
+#[tokio::test]
+async fn set_limits_applies_to_every_clone() {
+    let cache = ExpiringCache::new(Duration::from_secs(60), 4);
+    let clone = cache.clone();
+    cache.insert("size", 1);
+
+    clone.set_limits(Duration::from_millis(1), 1);
+    tokio::time::sleep(Duration::from_millis(5)).await;
+    assert_eq!(
+        cache.len_for(&"size"),
+        0,
+        "shorter TTL applies to the original"
+    );
+
+    // Restore the TTL so only the length limit trims the bucket.
+    clone.set_limits(Duration::from_secs(60), 1);
+    cache.insert("size", 2);
+    cache.insert("size", 3);
+    assert_eq!(cache.max_len(), 1);
+    assert_eq!(
+        clone.len_for(&"size"),
+        1,
+        "smaller max_len trims the original's inserts"
+    );
+}
+
+#[test]
+fn insert_aged_keeps_original_creation_time() {
+    let cache = ExpiringCache::new(Duration::from_secs(60), 4);
+    assert!(cache.insert_aged("size", Arc::new(1), Duration::from_secs(30)));
+    assert!(!cache.insert_aged("size", Arc::new(2), Duration::from_secs(61)));
+
+    let entries = cache.entries();
+    assert_eq!(entries.len(), 1);
+    let (key, value, age) = &entries[0];
+    assert_eq!((*key, **value), ("size", 1));
+    assert!(*age >= Duration::from_secs(30));
+}
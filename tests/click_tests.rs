@@ -0,0 +1,42 @@
+use slider_captcha_server::{verify_clicks, ClickPuzzle};
+
+#[test]
+fn click_puzzle_places_distinct_icons() {
+    let puzzle = ClickPuzzle::from_dimensions(500, 300, 4).unwrap();
+
+    assert_eq!(puzzle.icons.len(), 4);
+    assert_eq!(puzzle.points.len(), 4);
+    for (i, icon) in puzzle.icons.iter().enumerate() {
+        assert!(
+            !puzzle.icons[i + 1..].contains(icon),
+            "Icons should be distinct"
+        );
+    }
+    for &(x, y) in &puzzle.points {
+        assert!((0.0..=1.0).contains(&x) && (0.0..=1.0).contains(&y));
+    }
+    assert!(ClickPuzzle::from_dimensions(500, 300, 1).is_err());
+}
+
+#[test]
+fn verify_clicks_requires_order_and_tolerance() {
+    let solution = [(0.2, 0.3), (0.7, 0.6)];
+    let tolerance = (0.05, 0.05);
+
+    assert!(verify_clicks(
+        &solution,
+        &[(0.22, 0.28), (0.69, 0.63)],
+        tolerance
+    ));
+    assert!(!verify_clicks(
+        &solution,
+        &[(0.7, 0.6), (0.2, 0.3)],
+        tolerance
+    ));
+    assert!(!verify_clicks(
+        &solution,
+        &[(0.2, 0.3), (0.76, 0.6)],
+        tolerance
+    ));
+    assert!(!verify_clicks(&solution, &[(0.2, 0.3)], tolerance));
+}
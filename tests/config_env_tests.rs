@@ -0,0 +1,35 @@
+//! Kept apart from `config_tests` because it sets environment variables, which every
+//! `AppConfig::load` in the same process would see.
+
+use std::fs;
+
+use slider_captcha_server::config::AppConfig;
+
+#[test]
+fn sites_file_from_the_environment_replaces_inline_sites() {
+    let dir = std::env::temp_dir();
+    let config = dir.join(format!("{}-inline-sites.toml", std::process::id()));
+    fs::write(
+        &config,
+        "[[sites]]\nsite_key = \"inline\"\nsecret_key = \"inline-secret\"\n",
+    )
+    .unwrap();
+    let sites = dir.join(format!("{}-sites.json", std::process::id()));
+    fs::write(
+        &sites,
+        r#"[{ "site_key": "from-env", "secret_key": "env-secret" }]"#,
+    )
+    .unwrap();
+
+    std::env::set_var("PUZZLE_SITES_FILE", &sites);
+    let loaded = AppConfig::load(Some(&config));
+    std::env::remove_var("PUZZLE_SITES_FILE");
+
+    let loaded = loaded.unwrap();
+    assert_eq!(loaded.sites.len(), 1);
+    assert!(loaded.sites.get("from-env").is_some());
+    assert!(loaded.sites.get("inline").is_none());
+
+    fs::remove_file(config).unwrap();
+    fs::remove_file(sites).unwrap();
+}
@@ -0,0 +1,107 @@
+use std::{fs, path::PathBuf, time::Duration};
+
+use slider_captcha_server::{background::BackgroundStyle, config::AppConfig, render::Difficulty};
+
+fn write(name: &str, contents: &str) -> PathBuf {
+    let path = std::env::temp_dir().join(format!("{}-{name}", std::process::id()));
+    fs::write(&path, contents).unwrap();
+    path
+}
+
+#[test]
+fn file_settings_are_validated_and_round_trip() {
+    let path = write(
+        "config.toml",
+        r#"
+port = 9000
+solution_ttl_secs = 120
+prefill_dimensions = ["500x300", "320x200"]
+backgrounds = "noise:2,voronoi"
+difficulty = "hard"
+piece_glow = 0.35
+admin_token = "0123456789abcdef"
+
+[[sites]]
+site_key = "shop"
+secret_key = "shop-secret"
+allowed_sizes = ["500x300"]
+
+[[sites]]
+site_key = "blog"
+secret_key = "blog-secret"
+"#,
+    );
+    let config = AppConfig::load(Some(&path)).unwrap();
+    assert_eq!(config.port, 9000);
+    assert_eq!(config.solution_ttl, Duration::from_secs(120));
+    assert_eq!(config.prefill_dimensions, vec![(500, 300), (320, 200)]);
+    assert_eq!(
+        config.background_styles,
+        vec![(BackgroundStyle::Noise, 2), (BackgroundStyle::Voronoi, 1)]
+    );
+    assert_eq!(config.difficulty, Difficulty::Hard);
+    assert_eq!(
+        config.sites.get("shop").unwrap().allowed_sizes,
+        vec![(500, 300)]
+    );
+
+    let printed = config.to_toml();
+    assert!(printed.contains("piece_glow = 0.35\n"));
+    assert!(!printed.contains("shop-secret"));
+    assert!(!printed.contains("0123456789abcdef"));
+    assert!(printed.contains("secret_key = \"<redacted:shop>\"\n"));
+
+    // The printed form loads back to the same settings, secrets aside.
+    let reloaded = write("printed.toml", &printed);
+    assert_eq!(AppConfig::load(Some(&reloaded)).unwrap().to_toml(), printed);
+
+    fs::remove_file(path).unwrap();
+    fs::remove_file(reloaded).unwrap();
+}
+
+#[test]
+fn invalid_values_fail_with_their_source() {
+    for (name, contents, expected) in [
+        (
+            "bad.yaml",
+            "prefill_dimensions: 500x30O\n",
+            "prefill_dimensions: invalid size",
+        ),
+        (
+            "unknown.yaml",
+            "prefill: 500x300\n",
+            "prefill: unknown setting",
+        ),
+        (
+            "bool.toml",
+            "log_seeds = \"yes\"\n",
+            "log_seeds: expected true or false",
+        ),
+        (
+            "delivery.toml",
+            "image_delivery = \"link\"\n",
+            "image_delivery: expected inline or url",
+        ),
+        (
+            "quality.toml",
+            "image_quality = 0\n",
+            "image_quality: expected 1-100",
+        ),
+        (
+            "style.toml",
+            "backgrounds = \"noise,marble\"\n",
+            "unknown background style",
+        ),
+        ("prefill.toml", "cache_prefill = 64\n", "exceeds cache_max"),
+        (
+            "sites.toml",
+            "require_site_key = true\n",
+            "no sites are configured",
+        ),
+    ] {
+        let path = write(name, contents);
+        let err = AppConfig::load(Some(&path)).unwrap_err().to_string();
+        assert!(err.contains(expected), "{name}: {err}");
+        fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,63 @@
+use std::{fs, path::PathBuf, process::Command};
+
+fn generate(out: &PathBuf) -> String {
+    let _ = fs::remove_dir_all(out);
+    let status = Command::new(env!("CARGO_BIN_EXE_slider-captcha-gen"))
+        .args([
+            "--count",
+            "2",
+            "--sizes",
+            "200x150,240x160",
+            "--seed",
+            "42",
+            "--pieces",
+            "2",
+        ])
+        .arg("--out")
+        .arg(out)
+        .status()
+        .unwrap();
+    assert!(status.success());
+    fs::read_to_string(out.join("manifest.jsonl")).unwrap()
+}
+
+#[test]
+fn same_flags_produce_the_same_dataset() {
+    let base = std::env::temp_dir().join(format!("captcha-gen-{}", std::process::id()));
+    let first = generate(&base.join("a"));
+    let second = generate(&base.join("b"));
+    assert_eq!(first, second);
+
+    let entries: Vec<serde_json::Value> = first
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    assert_eq!(entries.len(), 4);
+    assert_eq!(entries[0]["seed"], 42);
+    assert_eq!(entries[3]["id"], "240x160-45");
+
+    for entry in &entries {
+        let pieces = entry["pieces"].as_array().unwrap();
+        assert_eq!(pieces.len(), 2);
+        for piece in pieces {
+            let path = base.join("a").join(piece["image"].as_str().unwrap());
+            assert_eq!(&fs::read(path).unwrap()[1..4], b"PNG");
+        }
+    }
+
+    fs::remove_dir_all(base).unwrap();
+}
+
+#[test]
+fn misspelled_background_style_is_rejected() {
+    let out = std::env::temp_dir().join(format!("captcha-gen-typo-{}", std::process::id()));
+    let output = Command::new(env!("CARGO_BIN_EXE_slider-captcha-gen"))
+        .args(["--count", "1", "--backgrounds", "gradient,nosie"])
+        .arg("--out")
+        .arg(&out)
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("unknown background style \"nosie\""));
+    assert!(!out.exists(), "nothing is generated");
+}
@@ -1,11 +1,17 @@
-use std::sync::Arc;
+use std::{sync::Arc, time::Duration};
 
-use slider_captcha_server::{config::AppConfig, generator::PuzzleGenerator};
+use slider_captcha_server::{
+    config::AppConfig,
+    generator::{
+        generate_puzzle, Answer, Challenge, ImageEncoding, PuzzleGenerator, PuzzleKey,
+        RenderOptions,
+    },
+    site::SiteRegistry,
+    PuzzleError,
+};
 
 fn test_config() -> AppConfig {
     AppConfig {
-        host: "127.0.0.1".into(),
-        port: 0,
         workers: 2,
         solution_ttl: std::time::Duration::from_secs(1),
         puzzle_ttl: std::time::Duration::from_secs(1),
@@ -14,8 +20,8 @@ fn test_config() -> AppConfig {
         generator_concurrency: 2,
         cleanup_interval: std::time::Duration::from_secs(60),
         prefill_dimensions: vec![(200, 200)],
-        log_level: "info".into(),
         immediate_cache_cleanup: false,
+        ..AppConfig::default()
     }
 }
 
@@ -25,11 +31,33 @@ async fn generator_prefills_cache() {
     let generator = PuzzleGenerator::new(config.clone());
     generator.fill_cache(&config);
 
-    tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+    // Debug builds generate slowly; poll instead of relying on a fixed sleep.
+    for _ in 0..50 {
+        if generator.total_cached() > 0 {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
 
     assert!(generator.total_cached() > 0);
 }
 
+#[tokio::test(flavor = "current_thread")]
+async fn negotiation_prefills_webp_buckets() {
+    let config = AppConfig {
+        negotiate_image_encoding: true,
+        ..test_config()
+    };
+    let generator = PuzzleGenerator::new(Arc::new(config.clone()));
+
+    let encodings: Vec<_> = generator
+        .prefill_levels(&config)
+        .into_iter()
+        .map(|(key, _)| key.encoding)
+        .collect();
+    assert_eq!(encodings, vec![ImageEncoding::Png, ImageEncoding::WebP]);
+}
+
 #[tokio::test(flavor = "current_thread")]
 async fn generator_get_puzzle_returns_value() {
     let config = Arc::new(test_config());
@@ -39,6 +67,225 @@ async fn generator_get_puzzle_returns_value() {
     tokio::time::sleep(std::time::Duration::from_millis(50)).await;
 
     let puzzle = generator.get_puzzle(200, 200).await;
-    assert!(puzzle.is_some());
+    assert!(puzzle.is_ok());
+}
+
+#[tokio::test(flavor = "current_thread")]
+async fn generator_serves_click_challenges() {
+    let config = Arc::new(test_config());
+    let generator = PuzzleGenerator::new(config.clone());
+
+    let images = generator
+        .get_challenge(PuzzleKey::click(300, 200, 3))
+        .await
+        .expect("click challenge");
+
+    match &images.challenge {
+        Challenge::Click { points, .. } => assert_eq!(points.len(), 3),
+        Challenge::Slider { .. } => panic!("Expected a click challenge"),
+    }
+}
+
+#[test]
+fn generate_puzzle_is_reproducible_from_seed() {
+    let key = PuzzleKey::click(300, 200, 3);
+    let first = generate_puzzle(key, 7, &RenderOptions::default()).unwrap();
+    let second = generate_puzzle(key, 7, &RenderOptions::default()).unwrap();
+
+    assert_eq!(first.seed, 7);
+    assert_eq!(first.background, second.background);
+    assert_eq!(first.pieces, second.pieces);
+}
+
+#[tokio::test(flavor = "current_thread")]
+async fn generator_rejects_invalid_keys_before_queueing() {
+    let config = Arc::new(test_config());
+    let generator = PuzzleGenerator::new(config.clone());
+
+    let err = generator.get_puzzle(10_000, 200).await.err().unwrap();
+    assert_eq!(
+        err,
+        PuzzleError::InvalidDimensions {
+            width: 10_000,
+            height: 200
+        }
+    );
+
+    let err = generator
+        .get_challenge(PuzzleKey::click(300, 200, 9))
+        .await
+        .err()
+        .unwrap();
+    assert!(matches!(err, PuzzleError::InvalidLayout(_)));
 }
 
+#[test]
+fn jpeg_encoding_keeps_piece_as_png() {
+    let key = PuzzleKey::slider(300, 200).with_encoding(ImageEncoding::Jpeg);
+    let images = generate_puzzle(key, 7, &RenderOptions::default()).unwrap();
+
+    assert_eq!(&images.background[..3], &[0xFF, 0xD8, 0xFF]);
+    assert_eq!(&images.pieces[0][1..4], b"PNG");
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn shutdown_cancels_queued_generations() {
+    let config = Arc::new(AppConfig {
+        generator_concurrency: 1,
+        ..test_config()
+    });
+    let generator = Arc::new(PuzzleGenerator::new(config));
+
+    let pending: Vec<_> = (0..4)
+        .map(|_| {
+            let generator = generator.clone();
+            tokio::spawn(async move { generator.get_puzzle(800, 600).await })
+        })
+        .collect();
+    tokio::time::sleep(Duration::from_millis(20)).await;
+    generator.shutdown();
+
+    let mut cancelled = 0;
+    for request in pending {
+        let result = tokio::time::timeout(Duration::from_secs(30), request)
+            .await
+            .expect("queued requests must not wait for generation")
+            .unwrap();
+        if result.is_err() {
+            assert_eq!(result.err(), Some(PuzzleError::ShuttingDown));
+            cancelled += 1;
+        }
+    }
+    assert!(cancelled >= 3, "only the running generation may finish");
+    assert_eq!(
+        generator.get_puzzle(200, 200).await.err(),
+        Some(PuzzleError::ShuttingDown)
+    );
+}
+
+#[tokio::test(flavor = "current_thread")]
+async fn snapshot_restores_unexpired_solutions_once() {
+    let config = Arc::new(test_config());
+    let path = std::env::temp_dir().join(format!("snapshot-{}.json", std::process::id()));
+    let images =
+        generate_puzzle(PuzzleKey::slider(200, 200), 7, &RenderOptions::default()).unwrap();
+    let answer = images.challenge.answer();
+
+    let generator = PuzzleGenerator::new(config.clone());
+    generator.cache_solution("live".into(), answer, 200, Some(Arc::new(images)), None);
+    generator.cache_solution(
+        "expired".into(),
+        Answer::Slider {
+            targets: vec![0.5],
+            orientation: Default::default(),
+        },
+        50,
+        None,
+        None,
+    );
+    generator.increment_attempts("live");
+    assert_eq!(generator.save_snapshot(&path, 100).unwrap(), (1, 0));
+
+    let restarted = PuzzleGenerator::new(config);
+    assert_eq!(
+        restarted
+            .restore_snapshot(&path, &SiteRegistry::default(), 100)
+            .unwrap(),
+        (1, 0)
+    );
+    let solution = restarted.get_solution("live").unwrap();
+    assert_eq!(solution.attempts, 1);
+    assert_eq!(solution.images.unwrap().seed, 7);
+    assert!(restarted.get_solution("expired").is_none());
+    assert!(!path.exists(), "a snapshot is consumed by restoring it");
+}
+
+#[tokio::test(flavor = "current_thread")]
+async fn snapshot_restore_skips_corrupted_records() {
+    let config = Arc::new(test_config());
+    let path = std::env::temp_dir().join(format!("corrupt-snapshot-{}.json", std::process::id()));
+    let generator = PuzzleGenerator::new(config.clone());
+    for (id, seed) in [("intact", 7), ("corrupted", 8)] {
+        let images =
+            generate_puzzle(PuzzleKey::slider(200, 200), seed, &RenderOptions::default()).unwrap();
+        let answer = images.challenge.answer();
+        generator.cache_solution(id.into(), answer, 200, Some(Arc::new(images)), None);
+    }
+    assert_eq!(generator.save_snapshot(&path, 100).unwrap(), (2, 0));
+
+    let mut snapshot: serde_json::Value =
+        serde_json::from_slice(&std::fs::read(&path).unwrap()).unwrap();
+    for record in snapshot["solutions"].as_array_mut().unwrap() {
+        if record["id"] == "corrupted" {
+            record["images"]["background"] = "not base64!".into();
+        }
+    }
+    std::fs::write(&path, serde_json::to_vec(&snapshot).unwrap()).unwrap();
+
+    let restarted = PuzzleGenerator::new(config);
+    assert_eq!(
+        restarted
+            .restore_snapshot(&path, &SiteRegistry::default(), 100)
+            .unwrap(),
+        (1, 0)
+    );
+    assert!(restarted.get_solution("intact").is_some());
+    assert!(restarted.get_solution("corrupted").is_none());
+    assert!(
+        !path.exists(),
+        "a snapshot with bad records is still consumed"
+    );
+}
+
+#[tokio::test(flavor = "current_thread")]
+async fn cache_snapshot_warms_a_restarted_generator() {
+    let config = Arc::new(AppConfig {
+        puzzle_ttl: Duration::from_secs(60),
+        ..test_config()
+    });
+    let key = PuzzleKey::slider(200, 200);
+    let path = std::env::temp_dir().join(format!("cache-snapshot-{}.json", std::process::id()));
+
+    let generator = PuzzleGenerator::new(config.clone());
+    generator.fill_cache(&config);
+    // Prefill must be done before snapshotting, or it could still add puzzles afterwards.
+    let mut prefilled = false;
+    for _ in 0..50 {
+        prefilled = generator.cache_len(&key) == config.cache_prefill_per_size;
+        if prefilled {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    assert!(prefilled, "prefill did not finish within 5s");
+    generator.cache_solution(
+        "pending".into(),
+        Answer::Slider {
+            targets: vec![0.5],
+            orientation: Default::default(),
+        },
+        2000,
+        None,
+        None,
+    );
+    let cached = generator.cache_len(&key);
+
+    // Periodic snapshots leave solutions out.
+    assert_eq!(generator.save_cache_snapshot(&path, 1000).unwrap(), cached);
+    let restarted = PuzzleGenerator::new(config.clone());
+    let restored = restarted
+        .restore_snapshot(&path, &SiteRegistry::default(), 1000)
+        .unwrap();
+    assert_eq!(restored, (0, cached));
+    assert_eq!(restarted.cache_len(&key), cached);
+    assert!(restarted.get_puzzle(200, 200).await.is_ok());
+
+    // Entries older than the cache TTL are dropped on restore.
+    generator.save_cache_snapshot(&path, 1000).unwrap();
+    let late = PuzzleGenerator::new(config);
+    assert_eq!(
+        late.restore_snapshot(&path, &SiteRegistry::default(), 1060)
+            .unwrap(),
+        (0, 0)
+    );
+}
@@ -0,0 +1,237 @@
+use image::{Rgba, RgbaImage};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use slider_captcha_server::{
+    background::BackgroundStyle,
+    render::{Difficulty, Perturbation, PieceStyle},
+    site::DEFAULT_TOLERANCE,
+    verify_pieces, ClickPuzzle, Orientation, PuzzleError, SliderPuzzle, MAX_DIMENSION,
+};
+
+fn flat_background(width: u32, height: u32) -> RgbaImage {
+    RgbaImage::from_pixel(width, height, Rgba([200, 200, 200, 255]))
+}
+
+#[test]
+fn multi_piece_puzzle_cuts_separate_holes() {
+    let mut rng = StdRng::seed_from_u64(3);
+    let puzzle =
+        SliderPuzzle::from_background(flat_background(500, 300), 3, Orientation::Horizontal, &mut rng)
+            .unwrap();
+    assert_eq!(puzzle.pieces.len(), 3);
+
+    let image = puzzle.cropped_puzzle.to_rgba8();
+    assert!(image.pixels().all(|pixel| pixel[3] == 255), "Holes must be opaque");
+    let shaded = image.pixels().filter(|pixel| pixel[0] < 200).count();
+    assert_eq!(shaded, 3 * 100 * 60, "Holes should not overlap");
+
+    assert!(SliderPuzzle::with_pieces(500, 300, 4).is_err());
+}
+
+#[test]
+fn styled_piece_has_outline_shadow_and_opaque_hole() {
+    let style = PieceStyle {
+        shadow_strength: 0.6,
+        ..PieceStyle::default()
+    };
+    let mut rng = StdRng::seed_from_u64(7);
+    let puzzle = SliderPuzzle::from_background_styled(
+        flat_background(400, 300),
+        1,
+        Orientation::Horizontal,
+        &style,
+        &Perturbation::default(),
+        &mut rng,
+    )
+    .unwrap();
+
+    let padding = style.padding();
+    assert!(padding > 0);
+    assert_eq!(puzzle.piece_padding, padding);
+
+    let piece = &puzzle.pieces[0];
+    let image = piece.image.to_rgba8();
+    assert_eq!(image.dimensions(), (80 + 2 * padding, 60 + 2 * padding));
+    assert_eq!(image.get_pixel(0, 0)[3], 0, "Shadow border starts transparent");
+    assert!(image.get_pixel(padding + 80, padding + 60)[3] > 0, "Shadow falls bottom-right");
+    assert!(image.get_pixel(padding, padding)[0] > 240, "Outline is drawn on the edge");
+    assert_eq!(image.get_pixel(padding + 40, padding + 30).0, [200, 200, 200, 255]);
+
+    let (x0, y0) = ((piece.x * 400.0).round() as u32, (piece.y * 300.0).round() as u32);
+    let background = puzzle.cropped_puzzle.to_rgba8();
+    assert_eq!(background.get_pixel(x0 + 40, y0 + 30).0, [140, 140, 140, 255]);
+    assert!(background.get_pixel(x0, y0 + 30)[0] < 140, "Inset shadow on the left edge");
+}
+
+#[test]
+fn difficulty_perturbs_the_piece_texture() {
+    let background =
+        BackgroundStyle::Noise
+            .generator()
+            .generate(500, 300, &mut StdRng::seed_from_u64(1));
+    let plain = PieceStyle {
+        outline_width: 0,
+        glow_strength: 0.0,
+        ..PieceStyle::default()
+    };
+    let render = |difficulty: Difficulty| {
+        SliderPuzzle::from_background_styled(
+            background.clone(),
+            1,
+            Orientation::Horizontal,
+            &plain,
+            &difficulty.perturbation(),
+            &mut StdRng::seed_from_u64(2),
+        )
+        .unwrap()
+    };
+    // Mean absolute difference between the piece and the background under its hole.
+    let mismatch = |puzzle: &SliderPuzzle| {
+        let piece = &puzzle.pieces[0];
+        let (x0, y0) = (
+            (piece.x * 500.0).round() as u32,
+            (piece.y * 300.0).round() as u32,
+        );
+        let image = piece.image.to_rgba8();
+        let total: u32 = image
+            .enumerate_pixels()
+            .flat_map(|(x, y, pixel)| {
+                let original = background.get_pixel(x0 + x, y0 + y);
+                (0..3).map(move |c| pixel[c].abs_diff(original[c]) as u32)
+            })
+            .sum();
+        total as f64 / (image.width() * image.height() * 3) as f64
+    };
+
+    assert_eq!(mismatch(&render(Difficulty::Off)), 0.0);
+    let hard = render(Difficulty::Hard);
+    assert!(
+        mismatch(&hard) > 3.0,
+        "Hard pieces must not be pixel copies"
+    );
+    assert_eq!(
+        hard.pieces[0].image,
+        render(Difficulty::Hard).pieces[0].image
+    );
+    assert_eq!(hard.pieces[0].x, render(Difficulty::Off).pieces[0].x);
+}
+
+#[test]
+fn hard_piece_aligned_on_its_texture_still_verifies() {
+    let plain = PieceStyle {
+        outline_width: 0,
+        glow_strength: 0.0,
+        ..PieceStyle::default()
+    };
+    for seed in 0..16 {
+        // Fine texture, so the piece only lines up in one place.
+        let mut texture = StdRng::seed_from_u64(seed);
+        let background = RgbaImage::from_fn(100, 100, |_, _| {
+            Rgba([texture.gen(), texture.gen(), texture.gen(), 255])
+        });
+        let puzzle = SliderPuzzle::from_background_styled(
+            background.clone(),
+            1,
+            Orientation::Horizontal,
+            &plain,
+            &Difficulty::Hard.perturbation(),
+            &mut StdRng::seed_from_u64(seed),
+        )
+        .unwrap();
+        let piece = &puzzle.pieces[0];
+        let image = piece.image.to_rgba8();
+        let (x0, y0) = (
+            (piece.x * 100.0).round() as u32,
+            (piece.y * 100.0).round() as u32,
+        );
+        // Slide to wherever the piece's texture best correlates with the background;
+        // normalising ignores the random colour gain, as a person would.
+        let correlation_at = |x: u32| -> f64 {
+            let brightness = |pixel: &Rgba<u8>| (0..3).map(|c| pixel[c] as f64).sum::<f64>();
+            let pairs: Vec<(f64, f64)> = image
+                .enumerate_pixels()
+                .map(|(px, py, pixel)| {
+                    (
+                        brightness(pixel),
+                        brightness(background.get_pixel(x + px, y0 + py)),
+                    )
+                })
+                .collect();
+            let count = pairs.len() as f64;
+            let mean_a = pairs.iter().map(|(a, _)| a).sum::<f64>() / count;
+            let mean_b = pairs.iter().map(|(_, b)| b).sum::<f64>() / count;
+            let (mut cross, mut var_a, mut var_b) = (0.0, 0.0, 0.0);
+            for (a, b) in &pairs {
+                cross += (a - mean_a) * (b - mean_b);
+                var_a += (a - mean_a).powi(2);
+                var_b += (b - mean_b).powi(2);
+            }
+            cross / (var_a * var_b).sqrt().max(f64::EPSILON)
+        };
+        let aligned = (x0.saturating_sub(5)..=(x0 + 5).min(100 - image.width()))
+            .max_by(|&a, &b| correlation_at(a).total_cmp(&correlation_at(b)))
+            .unwrap();
+
+        assert!(
+            verify_pieces(
+                &puzzle.targets(),
+                &[aligned as f64 / 100.0],
+                DEFAULT_TOLERANCE
+            ),
+            "seed {seed}: texture aligned at {aligned}px, hole at {x0}px"
+        );
+    }
+}
+
+#[test]
+fn verify_pieces_requires_every_piece() {
+    let solution = [0.2, 0.6];
+
+    assert!(verify_pieces(&solution, &[0.21, 0.595], 0.015));
+    assert!(!verify_pieces(&solution, &[0.21, 0.7], 0.015));
+    assert!(!verify_pieces(&solution, &[0.2], 0.015));
+}
+
+#[test]
+fn vertical_puzzle_targets_moving_axis() {
+    let puzzle = SliderPuzzle::with_layout(300, 500, 1, Orientation::Vertical).unwrap();
+    let piece = &puzzle.pieces[0];
+
+    assert_eq!(puzzle.targets(), vec![piece.y]);
+    assert!((0.2..0.4).contains(&piece.x), "Fixed x should stay in the band");
+}
+
+#[test]
+fn seeded_puzzles_are_reproducible() {
+    let first = SliderPuzzle::from_seed(500, 300, 42).unwrap();
+    let second = SliderPuzzle::from_seed(500, 300, 42).unwrap();
+    let other = SliderPuzzle::from_seed(500, 300, 43).unwrap();
+
+    assert_eq!(first.targets(), second.targets());
+    assert_eq!(first.cropped_puzzle.as_bytes(), second.cropped_puzzle.as_bytes());
+    assert_ne!(first.cropped_puzzle.as_bytes(), other.cropped_puzzle.as_bytes());
+}
+
+#[test]
+fn generation_never_panics_on_edge_dimensions() {
+    let sizes = [0, 1, 4, 19, 20, 21, 37, 100, MAX_DIMENSION + 1];
+    for &width in &sizes {
+        for &height in &sizes {
+            for pieces in 0..=4 {
+                for orientation in [Orientation::Horizontal, Orientation::Vertical] {
+                    let _ = SliderPuzzle::with_layout(width, height, pieces, orientation);
+                }
+            }
+            for icons in 0..=7 {
+                let _ = ClickPuzzle::from_dimensions(width, height, icons);
+            }
+        }
+    }
+
+    assert_eq!(
+        SliderPuzzle::from_dimensions(10, 300).unwrap_err(),
+        PuzzleError::InvalidDimensions {
+            width: 10,
+            height: 300
+        }
+    );
+}
@@ -0,0 +1,600 @@
+use std::{sync::Arc, time::Duration};
+
+use actix_web::{http::StatusCode, test, web, App};
+use slider_captcha_server::{
+    api::{ErrorCode, ErrorResponse, PuzzleResponse},
+    config::{AppConfig, ImageDelivery, SharedConfig},
+    generator::{Answer, PuzzleGenerator},
+    rate_limit::RateLimiter,
+    server::{configure, AppState},
+    site::{SiteConfig, SiteRegistry},
+};
+
+fn test_config(image_delivery: ImageDelivery) -> AppConfig {
+    AppConfig {
+        workers: 1,
+        solution_ttl: Duration::from_secs(60),
+        puzzle_ttl: Duration::from_secs(60),
+        cache_prefill_per_size: 1,
+        cache_max_per_size: 4,
+        generator_concurrency: 2,
+        cleanup_interval: Duration::from_secs(60),
+        prefill_dimensions: vec![],
+        image_delivery,
+        negotiate_image_encoding: true,
+        ..AppConfig::default()
+    }
+}
+
+fn test_state(image_delivery: ImageDelivery) -> AppState {
+    let config = Arc::new(test_config(image_delivery));
+    AppState {
+        generator: Arc::new(PuzzleGenerator::new(config.clone())),
+        config: SharedConfig::new(config),
+        rate_limiter: RateLimiter::new(),
+    }
+}
+
+#[actix_web::test]
+async fn url_delivery_serves_binary_images_until_solved() {
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(test_state(ImageDelivery::Url)))
+            .configure(configure),
+    )
+    .await;
+
+    let req = test::TestRequest::get()
+        .uri("/puzzle?w=200&h=150")
+        .to_request();
+    let body: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+    assert!(
+        body.get("puzzle_image").is_none(),
+        "URL mode must not inline images"
+    );
+
+    let id = body["id"].as_str().unwrap().to_string();
+    let piece_url = body["piece_url"].as_str().unwrap();
+    assert_eq!(piece_url, format!("/puzzle/{id}/piece.png"));
+
+    let req = test::TestRequest::get()
+        .uri(body["puzzle_url"].as_str().unwrap())
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    assert_eq!(resp.headers().get("content-type").unwrap(), "image/png");
+    assert_eq!(resp.headers().get("cache-control").unwrap(), "no-store");
+    let bytes = test::read_body(resp).await;
+    assert_eq!(&bytes[1..4], b"PNG");
+
+    let req = test::TestRequest::get()
+        .uri(&format!("/puzzle/{id}/other.png"))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+
+    // Exhausting the attempts discards the solution, and its images with it.
+    for _ in 0..5 {
+        let req = test::TestRequest::post()
+            .uri("/puzzle/solution")
+            .set_json(serde_json::json!({ "id": &id, "x": 2.0 }))
+            .to_request();
+        test::call_service(&app, req).await;
+    }
+
+    let req = test::TestRequest::get().uri(piece_url).to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+}
+
+#[actix_web::test]
+async fn inline_delivery_keeps_base64_response() {
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(test_state(ImageDelivery::Inline)))
+            .configure(configure),
+    )
+    .await;
+
+    let req = test::TestRequest::get()
+        .uri("/puzzle?w=200&h=150")
+        .to_request();
+    let body: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+    assert!(body["puzzle_image"].is_string());
+    assert!(body["piece_image"].is_string());
+
+    let id = body["id"].as_str().unwrap();
+    let req = test::TestRequest::get()
+        .uri(&format!("/puzzle/{id}/background.png"))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+}
+
+#[actix_web::test]
+async fn accept_header_selects_webp_and_reports_mime() {
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(test_state(ImageDelivery::Url)))
+            .configure(configure),
+    )
+    .await;
+
+    let req = test::TestRequest::get()
+        .uri("/puzzle?w=200&h=150")
+        .insert_header(("Accept", "image/webp,*/*"))
+        .to_request();
+    let body: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(body["puzzle_mime"], "image/webp");
+    assert_eq!(body["piece_mime"], "image/webp");
+
+    let req = test::TestRequest::get()
+        .uri(body["puzzle_url"].as_str().unwrap())
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.headers().get("content-type").unwrap(), "image/webp");
+    let bytes = test::read_body(resp).await;
+    assert_eq!(&bytes[8..12], b"WEBP");
+
+    let req = test::TestRequest::get()
+        .uri("/puzzle?w=200&h=150")
+        .insert_header(("Accept", "image/webp;q=0, image/png"))
+        .to_request();
+    let body: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(body["puzzle_mime"], "image/png");
+}
+
+#[actix_web::test]
+async fn verify_errors_carry_stable_codes_and_statuses() {
+    let state = test_state(ImageDelivery::Inline);
+    let generator = state.generator.clone();
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(state))
+            .configure(configure),
+    )
+    .await;
+
+    let req = test::TestRequest::get()
+        .uri("/puzzle?w=200&h=150")
+        .to_request();
+    let body: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+    let id = body["id"].as_str().unwrap().to_string();
+    let answer = generator.get_solution(&id).unwrap().answer;
+    generator.cache_solution("stale".into(), answer, 1, None, None);
+
+    let submit = |payload: serde_json::Value| {
+        test::TestRequest::post()
+            .uri("/puzzle/solution")
+            .set_json(payload)
+            .to_request()
+    };
+    for (payload, status, code) in [
+        (
+            serde_json::json!({ "id": "missing", "x": 1.0 }),
+            StatusCode::NOT_FOUND,
+            "INVALID_ID",
+        ),
+        (
+            serde_json::json!({ "id": "stale", "x": 1.0 }),
+            StatusCode::GONE,
+            "EXPIRED",
+        ),
+        (
+            serde_json::json!({ "x": 1.0 }),
+            StatusCode::BAD_REQUEST,
+            "INVALID_REQUEST",
+        ),
+        (
+            serde_json::json!({ "id": &id, "x": -1.0 }),
+            StatusCode::UNPROCESSABLE_ENTITY,
+            "WRONG_ANSWER",
+        ),
+    ] {
+        let resp = test::call_service(&app, submit(payload)).await;
+        assert_eq!(resp.status(), status, "{code}");
+        let body: serde_json::Value = test::read_body_json(resp).await;
+        assert_eq!(body["code"], code);
+        assert_eq!(body["version"], 1);
+        assert_eq!(body["success"], false);
+        assert!(body["error"].is_string());
+    }
+
+    let resp = test::call_service(&app, submit(serde_json::json!({ "id": &id, "x": -1.0 }))).await;
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(
+        body["details"],
+        serde_json::json!({ "attempts": 2, "remaining": 3 })
+    );
+}
+
+#[actix_web::test]
+async fn v1_routes_mirror_the_legacy_paths_and_publish_openapi() {
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(test_state(ImageDelivery::Url)))
+            .configure(configure),
+    )
+    .await;
+
+    let req = test::TestRequest::get()
+        .uri("/v1/puzzle?w=200&h=150")
+        .to_request();
+    let body: PuzzleResponse = test::call_and_read_body_json(&app, req).await;
+    let piece_url = body.piece_url.unwrap();
+    assert_eq!(piece_url, format!("/v1/puzzle/{}/piece.png", body.id));
+    let req = test::TestRequest::get().uri(&piece_url).to_request();
+    assert_eq!(test::call_service(&app, req).await.status(), StatusCode::OK);
+
+    let req = test::TestRequest::post()
+        .uri("/v1/puzzle/solution")
+        .set_json(serde_json::json!({ "id": "missing", "x": 0.5 }))
+        .to_request();
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+    let error: ErrorResponse = test::read_body_json(resp).await;
+    assert_eq!(error.code, ErrorCode::InvalidId);
+
+    let req = test::TestRequest::get()
+        .uri("/v1/openapi.json")
+        .to_request();
+    let spec: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+    assert!(spec["openapi"].as_str().unwrap().starts_with("3."));
+    for path in [
+        "/v1/puzzle",
+        "/v1/puzzle/{id}/{asset}",
+        "/v1/puzzle/solution",
+        "/v1/siteverify",
+    ] {
+        assert!(spec["paths"].get(path).is_some(), "{path}");
+    }
+    // `ALL` lists every variant, in order, under its wire name.
+    let codes = &spec["components"]["schemas"]["ErrorCode"]["enum"];
+    let names: Vec<_> = ErrorCode::ALL
+        .iter()
+        .map(|code| {
+            let name = serde_json::to_value(code).unwrap();
+            assert_eq!(name, code.to_string());
+            name
+        })
+        .collect();
+    assert_eq!(codes.as_array().unwrap(), &names);
+
+    for uri in ["/v1/nowhere", "/admin/nowhere", "/nowhere"] {
+        let resp = test::call_service(&app, test::TestRequest::get().uri(uri).to_request()).await;
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND, "{uri}");
+        let body: serde_json::Value = test::read_body_json(resp).await;
+        assert_eq!(body["code"], "NOT_FOUND", "{uri}");
+    }
+}
+
+fn site(site_key: &str, secret_key: &str) -> SiteConfig {
+    serde_json::from_value(serde_json::json!({
+        "site_key": site_key,
+        "secret_key": secret_key,
+        "allowed_sizes": ["200x150"],
+        "allowed_origins": ["https://a.example"],
+        "challenge_types": ["slider"],
+    }))
+    .unwrap()
+}
+
+#[actix_web::test]
+async fn site_keys_scope_solutions_to_the_issuing_site() {
+    let mut config = test_config(ImageDelivery::Inline);
+    config.sites =
+        SiteRegistry::new(vec![site("site-a", "secret-a"), site("site-b", "secret-b")]).unwrap();
+    config.require_site_key = true;
+    let config = Arc::new(config);
+    let generator = Arc::new(PuzzleGenerator::new(config.clone()));
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(AppState {
+                generator: generator.clone(),
+                config: SharedConfig::new(config),
+                rate_limiter: RateLimiter::new(),
+            }))
+            .configure(configure),
+    )
+    .await;
+
+    for (uri, origin, status) in [
+        (
+            "/puzzle?w=200&h=150",
+            "https://a.example",
+            StatusCode::FORBIDDEN,
+        ),
+        (
+            "/puzzle?w=200&h=150&sitekey=site-a",
+            "https://evil.example",
+            StatusCode::FORBIDDEN,
+        ),
+        (
+            "/puzzle?w=300&h=150&sitekey=site-a",
+            "https://a.example",
+            StatusCode::BAD_REQUEST,
+        ),
+        (
+            "/puzzle?w=200&h=150&type=click&sitekey=site-a",
+            "https://a.example",
+            StatusCode::BAD_REQUEST,
+        ),
+    ] {
+        let req = test::TestRequest::get()
+            .uri(uri)
+            .insert_header(("Origin", origin))
+            .to_request();
+        assert_eq!(
+            test::call_service(&app, req).await.status(),
+            status,
+            "{uri}"
+        );
+    }
+
+    let req = test::TestRequest::get()
+        .uri("/puzzle?w=200&h=150&sitekey=site-a")
+        .insert_header(("Origin", "https://a.example"))
+        .to_request();
+    let body: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+    let id = body["id"].as_str().unwrap().to_string();
+    let Some(Answer::Slider { targets, .. }) =
+        generator.get_solution(&id).map(|entry| entry.answer)
+    else {
+        panic!("expected a slider solution");
+    };
+
+    let solve = |sitekey: &str| {
+        test::TestRequest::post()
+            .uri("/puzzle/solution")
+            .set_json(serde_json::json!({ "id": &id, "x": targets[0], "sitekey": sitekey }))
+            .to_request()
+    };
+    let resp = test::call_service(&app, solve("site-b")).await;
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+    let resp = test::call_service(&app, solve("site-a")).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+
+    let siteverify = |secret: &str| {
+        test::TestRequest::post()
+            .uri("/siteverify")
+            .set_json(serde_json::json!({ "id": &id, "secret": secret }))
+            .to_request()
+    };
+    let resp = test::call_service(&app, siteverify("wrong")).await;
+    assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+    let resp = test::call_service(&app, siteverify("secret-b")).await;
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+    let body: serde_json::Value = test::call_and_read_body_json(&app, siteverify("secret-a")).await;
+    assert_eq!(body["success"], true);
+    assert_eq!(body["sitekey"], "site-a");
+    let resp = test::call_service(&app, siteverify("secret-a")).await;
+    assert_eq!(
+        resp.status(),
+        StatusCode::NOT_FOUND,
+        "tokens are single-use"
+    );
+}
+
+#[actix_web::test]
+async fn admin_reload_swaps_config_and_keeps_solutions() {
+    let path = std::env::temp_dir().join(format!("reload-{}.toml", std::process::id()));
+    let settings = |extra: &str| {
+        format!(
+            "admin_token = \"0123456789abcdef\"\nprefill_dimensions = \"\"\ncache_prefill = 1\ncache_max = 4\n{extra}"
+        )
+    };
+    std::fs::write(&path, settings("solution_ttl_secs = 60\n")).unwrap();
+
+    let shared = SharedConfig::load_from(Some(path.clone())).unwrap();
+    let generator = Arc::new(PuzzleGenerator::new(shared.load()));
+    let state = AppState {
+        generator: generator.clone(),
+        config: shared.clone(),
+        rate_limiter: RateLimiter::new(),
+    };
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(state))
+            .configure(configure),
+    )
+    .await;
+
+    let req = test::TestRequest::get()
+        .uri("/puzzle?w=200&h=150")
+        .to_request();
+    let body: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+    let id = body["id"].as_str().unwrap().to_string();
+
+    let reload = |token: &str| {
+        test::TestRequest::post()
+            .uri("/admin/reload")
+            .insert_header(("Authorization", format!("Bearer {token}")))
+            .to_request()
+    };
+    let resp = test::call_service(&app, reload("wrong-token-0000")).await;
+    assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+
+    std::fs::write(
+        &path,
+        settings("solution_ttl_secs = 120\ngenerator_concurrency = 4\nport = 9999\n"),
+    )
+    .unwrap();
+    let body: serde_json::Value =
+        test::call_and_read_body_json(&app, reload("0123456789abcdef")).await;
+    assert_eq!(body["restart_required"], serde_json::json!(["port"]));
+    assert_eq!(shared.load().solution_ttl, Duration::from_secs(120));
+    assert!(
+        generator.get_solution(&id).is_some(),
+        "reload must keep solutions"
+    );
+
+    std::fs::write(&path, settings("solution_ttl_secs = 0\n")).unwrap();
+    let resp = test::call_service(&app, reload("0123456789abcdef")).await;
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+    assert_eq!(shared.load().solution_ttl, Duration::from_secs(120));
+
+    std::fs::remove_file(path).unwrap();
+}
+
+#[actix_web::test]
+async fn readiness_waits_for_prefill_and_fails_on_shutdown() {
+    let config = Arc::new(AppConfig {
+        prefill_dimensions: vec![(200, 150)],
+        ..test_config(ImageDelivery::Inline)
+    });
+    let generator = Arc::new(PuzzleGenerator::new(config.clone()));
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(AppState {
+                generator: generator.clone(),
+                config: SharedConfig::new(config.clone()),
+                rate_limiter: RateLimiter::new(),
+            }))
+            .configure(configure),
+    )
+    .await;
+    let probe = |uri: &'static str| test::TestRequest::get().uri(uri).to_request();
+
+    let resp = test::call_service(&app, probe("/health/ready")).await;
+    assert_eq!(resp.status(), StatusCode::SERVICE_UNAVAILABLE);
+    let body: serde_json::Value = test::read_body_json(resp).await;
+    assert_eq!(body["checks"]["prefill"]["reached"], false);
+    assert_eq!(body["checks"]["prefill"]["sizes"][0]["size"], "200x150");
+    assert_eq!(body["checks"]["dispatcher"], "running");
+    assert_eq!(body["checks"]["solutions"]["connected"], true);
+    let resp = test::call_service(&app, probe("/health/live")).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+
+    generator.fill_cache(&config);
+    let mut ready = false;
+    for _ in 0..100 {
+        let resp = test::call_service(&app, probe("/health/ready")).await;
+        if resp.status() == StatusCode::OK {
+            ready = true;
+            break;
+        }
+        actix_web::rt::time::sleep(Duration::from_millis(50)).await;
+    }
+    assert!(ready, "prefill never reached the readiness minimum");
+
+    generator.shutdown();
+    let resp = test::call_service(&app, probe("/health/ready")).await;
+    assert_eq!(resp.status(), StatusCode::SERVICE_UNAVAILABLE);
+    let body: serde_json::Value = test::call_and_read_body_json(&app, probe("/health/live")).await;
+    assert_eq!(body["status"], "ok");
+    assert_eq!(body["checks"]["shutting_down"], true);
+}
+
+#[actix_web::test]
+async fn admin_scope_manages_cache_solutions_and_settings() {
+    let config = Arc::new(AppConfig {
+        admin_token: Some("0123456789abcdef".into()),
+        ..test_config(ImageDelivery::Inline)
+    });
+    let app = test::init_service(
+        App::new()
+            .app_data(web::Data::new(AppState {
+                generator: Arc::new(PuzzleGenerator::new(config.clone())),
+                config: SharedConfig::new(config),
+                rate_limiter: RateLimiter::new(),
+            }))
+            .configure(configure),
+    )
+    .await;
+    let admin = |req: test::TestRequest| {
+        req.insert_header(("Authorization", "Bearer 0123456789abcdef"))
+            .to_request()
+    };
+    let new_puzzle = || {
+        test::TestRequest::get()
+            .uri("/puzzle?w=200&h=150")
+            .peer_addr("10.0.0.1:4000".parse().unwrap())
+            .to_request()
+    };
+
+    let resp = test::call_service(
+        &app,
+        test::TestRequest::get().uri("/admin/cache").to_request(),
+    )
+    .await;
+    assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+
+    let body: serde_json::Value = test::call_and_read_body_json(&app, new_puzzle()).await;
+    let id = body["id"].as_str().unwrap().to_string();
+
+    let req = admin(test::TestRequest::post().uri("/admin/cache/refill?w=200&h=150&count=2"));
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::ACCEPTED);
+    let body: serde_json::Value =
+        test::call_and_read_body_json(&app, admin(test::TestRequest::get().uri("/admin/cache")))
+            .await;
+    assert!(body["buckets"].as_array().is_some());
+    let req = admin(test::TestRequest::delete().uri("/admin/cache?w=200&h=150"));
+    let body: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(body["bucket"]["size"], "200x150");
+    // Small sizes map to the bucket `/puzzle` serves them from.
+    let req = admin(test::TestRequest::delete().uri("/admin/cache?w=50&h=150"));
+    let body: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(body["bucket"]["size"], "100x150");
+
+    let uri = format!("/admin/solutions/{id}");
+    let body: serde_json::Value =
+        test::call_and_read_body_json(&app, admin(test::TestRequest::get().uri(&uri))).await;
+    assert_eq!(body["status"], "pending");
+    assert!(
+        body.get("answer").is_none(),
+        "answers stay hidden unless requested"
+    );
+    let revealed = format!("{uri}?reveal=true");
+    let body: serde_json::Value =
+        test::call_and_read_body_json(&app, admin(test::TestRequest::get().uri(&revealed))).await;
+    assert!(body.get("answer").is_some());
+
+    let req = admin(
+        test::TestRequest::patch()
+            .uri("/admin/settings")
+            .set_json(serde_json::json!({ "max_attempts": 1, "rate_limit_per_minute": 1 })),
+    );
+    let body: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(body["max_attempts"], 1);
+    let req = admin(
+        test::TestRequest::patch()
+            .uri("/admin/settings")
+            .set_json(serde_json::json!({ "difficulty": "extreme" })),
+    );
+    let resp = test::call_service(&app, req).await;
+    assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+
+    // One wrong answer now discards the id.
+    let req = test::TestRequest::post()
+        .uri("/puzzle/solution")
+        .set_json(serde_json::json!({ "id": id, "x": -1.0 }))
+        .to_request();
+    let body: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(body["code"], "TOO_MANY_ATTEMPTS");
+    assert_eq!(body["details"]["attempts"], 1);
+    let resp = test::call_service(&app, admin(test::TestRequest::get().uri(&uri))).await;
+    assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+
+    // Requests only count once a limit is set: one per minute from this client.
+    let resp = test::call_service(&app, new_puzzle()).await;
+    assert_eq!(resp.status(), StatusCode::OK);
+    let resp = test::call_service(&app, new_puzzle()).await;
+    assert_eq!(resp.status(), StatusCode::TOO_MANY_REQUESTS);
+    assert!(resp.headers().contains_key("retry-after"));
+
+    let other = test::TestRequest::get()
+        .uri("/puzzle?w=200&h=150")
+        .peer_addr("10.0.0.2:4000".parse().unwrap())
+        .to_request();
+    let body: serde_json::Value = test::call_and_read_body_json(&app, other).await;
+    let other_id = body["id"].as_str().unwrap().to_string();
+    let req = admin(
+        test::TestRequest::post()
+            .uri("/admin/revoke")
+            .set_json(serde_json::json!({ "ids": [other_id, "missing"] })),
+    );
+    let body: serde_json::Value = test::call_and_read_body_json(&app, req).await;
+    assert_eq!(body["revoked"], serde_json::json!([other_id]));
+    assert_eq!(body["unknown"], serde_json::json!(["missing"]));
+}
@@ -0,0 +1,51 @@
+use slider_captcha_server::{
+    background::{BackgroundStyle, WeightedBackgrounds},
+    render::{Difficulty, PieceStyle},
+    solver::{evaluate, standard_attacks, SolverConfig},
+    Orientation,
+};
+
+fn config(style: BackgroundStyle) -> SolverConfig {
+    SolverConfig {
+        width: 200,
+        height: 150,
+        pieces: 1,
+        orientation: Orientation::Horizontal,
+        backgrounds: WeightedBackgrounds::from_styles(&[(style, 1)]),
+        piece_style: PieceStyle::default(),
+        difficulty: Difficulty::Off,
+    }
+}
+
+#[test]
+fn reports_cover_every_attack_and_seed() {
+    let attacks = standard_attacks();
+    let reports = evaluate(&config(BackgroundStyle::Noise), 0..5, &attacks).unwrap();
+
+    assert_eq!(reports.len(), attacks.len());
+    for (report, attack) in reports.iter().zip(&attacks) {
+        assert_eq!(report.attack, attack.name());
+        assert_eq!(report.total, 5);
+        assert!(report.solved <= report.total);
+    }
+}
+
+#[test]
+fn opaque_holes_defeat_alpha_scan_but_not_template_match() {
+    let reports = evaluate(
+        &config(BackgroundStyle::Voronoi),
+        0..10,
+        &standard_attacks(),
+    )
+    .unwrap();
+    let rate = |name: &str| {
+        reports
+            .iter()
+            .find(|report| report.attack == name)
+            .unwrap()
+            .rate()
+    };
+
+    assert_eq!(rate("alpha-scan"), 0.0);
+    assert!(rate("template-match") >= 0.8);
+}